@@ -0,0 +1,26 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AttendanceStatus {
+    Present,
+    Late,
+    NoShow,
+    Excused,
+}
+
+/// Whether a member who signed up for a practice showed up. There is at most
+/// one record per member and practice; checking in again overwrites it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Attendance {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub practice_id: ObjectId,
+    pub user_id: ObjectId,
+    /// Copied from the practice so history can be sorted and windowed
+    /// without looking every practice up.
+    pub practice_start: DateTime<Utc>,
+    pub status: AttendanceStatus,
+    pub recorded_at: DateTime<Utc>,
+}
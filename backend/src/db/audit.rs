@@ -0,0 +1,163 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use super::practice::{ManualLock, Placement, Practice};
+use super::user::Side;
+
+/// Where a roster change came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuditSource {
+    Bot,
+    Api,
+    SheetSync,
+    Job,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuditAction {
+    Signup,
+    Unregister,
+    /// Moved off the waitlist into a seat someone else left.
+    Promote,
+    /// A seat handed over or swapped between members.
+    Transfer,
+    /// An exec changing the roster directly.
+    AdminOverride,
+    LotteryDraw,
+    /// Flex paddlers moved across sides at lock time.
+    Rebalance,
+    /// The previous practice's waitlist carried into priority seats.
+    WaitlistCarryOver,
+    SheetImport,
+}
+
+/// Who changed a roster, how, and why. Attached to every roster write.
+#[derive(Clone, Copy, Debug)]
+pub struct RosterChange {
+    /// `None` for jobs and syncs.
+    pub actor: Option<ObjectId>,
+    /// The member an exec moved, when it isn't the actor themselves.
+    pub subject: Option<ObjectId>,
+    pub source: AuditSource,
+    pub action: AuditAction,
+}
+
+impl RosterChange {
+    pub fn job(action: AuditAction) -> Self {
+        Self {
+            actor: None,
+            subject: None,
+            source: AuditSource::Job,
+            action,
+        }
+    }
+
+    /// The same change, made for `user_id`.
+    pub fn about(self, user_id: ObjectId) -> Self {
+        Self {
+            subject: Some(user_id),
+            ..self
+        }
+    }
+}
+
+/// One member's move on one practice, or an exec locking or reopening its
+/// signups. Entries are only ever inserted.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuditEntry {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub at: DateTime<Utc>,
+    pub practice_id: ObjectId,
+    pub user_id: ObjectId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<ObjectId>,
+    pub source: AuditSource,
+    pub action: AuditAction,
+    /// Side and seat or waitlist index the member ended up in, or left.
+    pub side: Option<Side>,
+    pub seat: Option<usize>,
+    pub before: Option<Placement>,
+    pub after: Option<Placement>,
+    /// The manual lock before and after a lock change, where `None` is the
+    /// signup window. `user_id` is then the exec who changed it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock_before: Option<ManualLock>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock_after: Option<ManualLock>,
+}
+
+/// An entry for every member whose place on the roster differs between the
+/// two versions of a practice, and one for the actor if they changed the
+/// manual lock. Members pulled off the waitlist as a side effect of someone
+/// else's change are recorded as promoted.
+pub fn roster_changes(before: Option<&Practice>, after: &Practice, change: &RosterChange) -> Vec<AuditEntry> {
+    let Some(practice_id) = after.id else {
+        return Vec::new();
+    };
+    let at = Utc::now();
+
+    let lock_before = before.and_then(|practice| practice.manual_lock);
+    let lock_after = after.manual_lock;
+    let lock_change = change
+        .actor
+        .filter(|_| lock_before != lock_after)
+        .map(|user_id| AuditEntry {
+            id: None,
+            at,
+            practice_id,
+            user_id,
+            actor: change.actor,
+            source: change.source,
+            action: change.action,
+            side: None,
+            seat: None,
+            before: None,
+            after: None,
+            lock_before,
+            lock_after,
+        });
+
+    let before = before.map(Practice::placements).unwrap_or_default();
+    let mut after = after.placements();
+
+    let mut user_ids = before.keys().chain(after.keys()).copied().collect::<Vec<_>>();
+    user_ids.sort();
+    user_ids.dedup();
+
+    user_ids
+        .into_iter()
+        .filter_map(|user_id| {
+            let was = before.get(&user_id).cloned();
+            let now = after.remove(&user_id);
+            if was == now {
+                return None;
+            }
+
+            let promoted = matches!(was, Some(Placement::Waitlist { .. }))
+                && now.as_ref().is_some_and(Placement::is_seated)
+                && change.actor != Some(user_id)
+                && change.subject != Some(user_id)
+                && change.action != AuditAction::Transfer;
+            let placement = now.as_ref().or(was.as_ref());
+
+            Some(AuditEntry {
+                id: None,
+                at,
+                practice_id,
+                user_id,
+                actor: change.actor,
+                source: change.source,
+                action: if promoted { AuditAction::Promote } else { change.action },
+                side: placement.and_then(Placement::side),
+                seat: placement.and_then(Placement::index),
+                before: was,
+                after: now,
+                lock_before: None,
+                lock_after: None,
+            })
+        })
+        .chain(lock_change)
+        .collect()
+}
@@ -0,0 +1,538 @@
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::America::New_York;
+use serde::{Deserialize, Serialize};
+
+use super::attendance::{Attendance, AttendanceStatus};
+use super::practice::PracticeType;
+use super::user::{Membership, MembershipStatus, SignupTier, User};
+
+/// When signups open and close and when the previous waitlist is carried
+/// over, as minutes before a practice starts.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct SignupWindow {
+    pub opens_minutes_before: i64,
+    pub closes_minutes_before: i64,
+    pub waitlist_transfer_minutes_before: i64,
+}
+
+/// Furthest ahead of a practice its signup times can be set.
+const MAX_SIGNUP_WINDOW_MINUTES: i64 = 60 * 24 * 60;
+
+/// Furthest ahead of general signups early access can open.
+const MAX_EARLY_ACCESS_MINUTES: i64 = 14 * 24 * 60;
+
+/// Longest a no-show policy can look back.
+const MAX_NO_SHOW_WINDOW_DAYS: i64 = 365;
+
+/// Longest a no-show penalty can hold back a member's signups.
+const MAX_SIGNUP_DELAY_HOURS: i64 = 14 * 24;
+
+impl SignupWindow {
+    /// Checks the times are in the order a practice needs: the waitlist
+    /// transfers no later than signups open, which is before they close,
+    /// which is no later than the start.
+    pub fn validate(&self) -> Result<(), String> {
+        let before = |minutes: i64| {
+            (0..=MAX_SIGNUP_WINDOW_MINUTES)
+                .contains(&minutes)
+                .then(|| Duration::try_minutes(minutes))
+                .flatten()
+        };
+        let (Some(opens), Some(closes), Some(transfer)) = (
+            before(self.opens_minutes_before),
+            before(self.closes_minutes_before),
+            before(self.waitlist_transfer_minutes_before),
+        ) else {
+            return Err(format!(
+                "Signup windows must be within {} days before a practice",
+                MAX_SIGNUP_WINDOW_MINUTES / (24 * 60)
+            ));
+        };
+
+        if opens <= closes {
+            return Err("Signups must open before they close".to_string());
+        }
+        if transfer < opens {
+            return Err("Waitlists must transfer no later than signups open".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn opens_before(&self) -> Duration {
+        Duration::minutes(self.opens_minutes_before)
+    }
+
+    pub fn closes_before(&self) -> Duration {
+        Duration::minutes(self.closes_minutes_before)
+    }
+
+    pub fn waitlist_transfer_before(&self) -> Duration {
+        Duration::minutes(self.waitlist_transfer_minutes_before)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SignupWindowOverride {
+    pub practice_type: PracticeType,
+    pub window: SignupWindow,
+}
+
+/// Opens signups for a tier this many minutes before everyone else.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct EarlyAccess {
+    pub tier: SignupTier,
+    pub minutes_before: i64,
+}
+
+impl EarlyAccess {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_EARLY_ACCESS_MINUTES).contains(&self.minutes_before) {
+            return Err(format!(
+                "Early access must open between 1 minute and {} days before general signups",
+                MAX_EARLY_ACCESS_MINUTES / (24 * 60)
+            ));
+        }
+        Ok(())
+    }
+
+    /// When signups open for the tier, given when they open for everyone.
+    /// A lead out of range gives no head start rather than overflowing.
+    pub fn opens_at(&self, signup_opens_at: DateTime<Utc>) -> DateTime<Utc> {
+        Duration::try_minutes(self.minutes_before)
+            .and_then(|lead| signup_opens_at.checked_sub_signed(lead))
+            .unwrap_or(signup_opens_at)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NoShowPenalty {
+    /// Signups open this many hours later for the member.
+    DelayedSignup { hours: i64 },
+    /// The member can only join the waitlist.
+    WaitlistOnly,
+}
+
+impl NoShowPenalty {
+    fn severity(&self) -> i64 {
+        match self {
+            NoShowPenalty::DelayedSignup { hours } => *hours,
+            NoShowPenalty::WaitlistOnly => i64::MAX,
+        }
+    }
+}
+
+/// Penalizes members with at least `no_shows` no-shows over the last
+/// `window_days` days.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NoShowPolicy {
+    pub no_shows: usize,
+    pub window_days: i64,
+    pub penalty: NoShowPenalty,
+}
+
+/// A policy that applies to a member, with the count that triggered it.
+#[derive(Clone, Debug, Serialize)]
+pub struct AppliedPenalty {
+    pub penalty: NoShowPenalty,
+    pub no_shows: usize,
+    pub window_days: i64,
+}
+
+impl AppliedPenalty {
+    /// Why the member is penalized, for telling them in a signup response.
+    pub fn reason(&self) -> String {
+        format!(
+            "you didn't show up to {} practices in the last {} days",
+            self.no_shows, self.window_days
+        )
+    }
+}
+
+/// What a member's record lets them do when joining a practice's roster.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SignupEligibility {
+    Allowed,
+    /// Only waitlist spots, for the given reason.
+    WaitlistOnly(String),
+    /// Nothing for now, with the message to show the member.
+    Refused(String),
+}
+
+/// How many guests can come to a practice. Execs running trial practices
+/// aren't held to the per-member limit.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct GuestPolicy {
+    pub per_practice: usize,
+    pub per_member: usize,
+}
+
+impl Default for GuestPolicy {
+    fn default() -> Self {
+        Self {
+            per_practice: 4,
+            per_member: 1,
+        }
+    }
+}
+
+/// What members without a paid membership can still do once their free
+/// trial practices are used up.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnpaidSignup {
+    #[default]
+    Refused,
+    WaitlistOnly,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct MembershipPolicy {
+    /// Practices a member can attend before paying.
+    pub free_trial_practices: usize,
+    #[serde(default)]
+    pub unpaid: UnpaidSignup,
+}
+
+impl Default for MembershipPolicy {
+    fn default() -> Self {
+        Self {
+            free_trial_practices: 2,
+            unpaid: UnpaidSignup::Refused,
+        }
+    }
+}
+
+/// Club-wide defaults, stored as a single document.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ClubSettings {
+    #[serde(default)]
+    pub signup_windows: Vec<SignupWindowOverride>,
+    #[serde(default)]
+    pub no_show_policies: Vec<NoShowPolicy>,
+    /// Copied onto practices when they're created.
+    #[serde(default)]
+    pub early_access: Vec<EarlyAccess>,
+    #[serde(default)]
+    pub guests: GuestPolicy,
+    #[serde(default)]
+    pub membership: MembershipPolicy,
+}
+
+impl ClubSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        for entry in &self.signup_windows {
+            entry
+                .window
+                .validate()
+                .map_err(|e| format!("{:?} signup window: {}", entry.practice_type, e))?;
+        }
+        for policy in &self.no_show_policies {
+            if policy.no_shows == 0 || !(1..=MAX_NO_SHOW_WINDOW_DAYS).contains(&policy.window_days) {
+                return Err(format!(
+                    "No-show policies need at least one no-show over 1 to {} days",
+                    MAX_NO_SHOW_WINDOW_DAYS
+                ));
+            }
+            if let NoShowPenalty::DelayedSignup { hours } = policy.penalty {
+                if !(1..=MAX_SIGNUP_DELAY_HOURS).contains(&hours) {
+                    return Err(format!(
+                        "Signup delays must be between 1 and {} hours",
+                        MAX_SIGNUP_DELAY_HOURS
+                    ));
+                }
+            }
+        }
+        for early in &self.early_access {
+            early.validate()?;
+        }
+        Ok(())
+    }
+
+    pub fn signup_window(&self, practice_type: PracticeType) -> SignupWindow {
+        self.signup_windows
+            .iter()
+            .find(|entry| entry.practice_type == practice_type)
+            .map(|entry| entry.window)
+            .unwrap_or_else(|| practice_type.default_signup_window())
+    }
+
+    /// How an unpaid member's signups are limited, once they've been to all
+    /// their free trial practices.
+    pub fn unpaid_restriction(
+        &self,
+        membership: &Membership,
+        history: &[Attendance],
+        now: DateTime<Utc>,
+    ) -> Option<UnpaidSignup> {
+        if membership.status_at(now) == MembershipStatus::Paid {
+            return None;
+        }
+
+        let attended = history
+            .iter()
+            .filter(|record| matches!(record.status, AttendanceStatus::Present | AttendanceStatus::Late))
+            .count();
+
+        (attended >= self.membership.free_trial_practices).then_some(self.membership.unpaid)
+    }
+
+    /// Checks a member's membership and no-show record against a practice
+    /// whose signups open for them at `opens_at`. Every way onto a roster goes
+    /// through this: signups, accepted seat offers and lottery draws.
+    pub fn signup_eligibility(
+        &self,
+        user: &User,
+        history: &[Attendance],
+        opens_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> SignupEligibility {
+        let unpaid_reason = format!(
+            "you've used your {} free trial practices and your membership isn't paid",
+            self.membership.free_trial_practices
+        );
+        let unpaid = match self.unpaid_restriction(&user.membership, history, now) {
+            Some(UnpaidSignup::Refused) => {
+                return SignupEligibility::Refused(format!("You can't sign up because {}", unpaid_reason))
+            }
+            Some(UnpaidSignup::WaitlistOnly) => Some(unpaid_reason),
+            None => None,
+        };
+
+        match self.penalty_eligibility(user, history, opens_at, now) {
+            SignupEligibility::Allowed => {
+                unpaid.map_or(SignupEligibility::Allowed, SignupEligibility::WaitlistOnly)
+            }
+            limited => limited,
+        }
+    }
+
+    fn penalty_eligibility(
+        &self,
+        user: &User,
+        history: &[Attendance],
+        opens_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> SignupEligibility {
+        if user.penalty_waived_until.is_some_and(|until| until > now) {
+            return SignupEligibility::Allowed;
+        }
+        let Some(applied) = self.no_show_penalty(history, now) else {
+            return SignupEligibility::Allowed;
+        };
+
+        match applied.penalty {
+            NoShowPenalty::DelayedSignup { hours } => {
+                match Duration::try_hours(hours).and_then(|delay| opens_at.checked_add_signed(delay)) {
+                    Some(delayed) if now >= delayed => SignupEligibility::Allowed,
+                    Some(delayed) => SignupEligibility::Refused(format!(
+                        "Signups open for you on {} because {}",
+                        delayed.with_timezone(&New_York).format("%A, %B %d at %I:%M %p"),
+                        applied.reason()
+                    )),
+                    None => SignupEligibility::Refused(format!(
+                        "You can't sign up yet because {}",
+                        applied.reason()
+                    )),
+                }
+            }
+            NoShowPenalty::WaitlistOnly => SignupEligibility::WaitlistOnly(applied.reason()),
+        }
+    }
+
+    /// The most severe no-show penalty a member's attendance history earns
+    /// them, if any. Excused absences never count.
+    pub fn no_show_penalty(
+        &self,
+        history: &[Attendance],
+        now: DateTime<Utc>,
+    ) -> Option<AppliedPenalty> {
+        self.no_show_policies
+            .iter()
+            .filter_map(|policy| {
+                let since = now.checked_sub_signed(Duration::try_days(policy.window_days)?)?;
+                let no_shows = history
+                    .iter()
+                    .filter(|record| record.status == AttendanceStatus::NoShow)
+                    .filter(|record| record.practice_start >= since && record.practice_start <= now)
+                    .count();
+
+                (no_shows >= policy.no_shows).then_some(AppliedPenalty {
+                    penalty: policy.penalty,
+                    no_shows,
+                    window_days: policy.window_days,
+                })
+            })
+            .max_by_key(|applied| applied.penalty.severity())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::oid::ObjectId;
+
+    fn record(status: AttendanceStatus, days_ago: i64, now: DateTime<Utc>) -> Attendance {
+        Attendance {
+            id: None,
+            practice_id: ObjectId::new(),
+            user_id: ObjectId::new(),
+            practice_start: now - Duration::days(days_ago),
+            status,
+            recorded_at: now,
+        }
+    }
+
+    fn with_policies(no_show_policies: Vec<NoShowPolicy>) -> ClubSettings {
+        ClubSettings {
+            no_show_policies,
+            ..Default::default()
+        }
+    }
+
+    fn policy(no_shows: usize, penalty: NoShowPenalty) -> NoShowPolicy {
+        NoShowPolicy {
+            no_shows,
+            window_days: 30,
+            penalty,
+        }
+    }
+
+    #[test]
+    fn no_penalty_below_the_threshold() {
+        let now = Utc::now();
+        let settings = with_policies(vec![policy(2, NoShowPenalty::WaitlistOnly)]);
+        let history = [
+            record(AttendanceStatus::NoShow, 3, now),
+            record(AttendanceStatus::Present, 10, now),
+        ];
+
+        assert!(settings.no_show_penalty(&history, now).is_none());
+    }
+
+    #[test]
+    fn only_no_shows_inside_the_window_count() {
+        let now = Utc::now();
+        let settings = with_policies(vec![policy(2, NoShowPenalty::WaitlistOnly)]);
+
+        let old = [
+            record(AttendanceStatus::NoShow, 3, now),
+            record(AttendanceStatus::NoShow, 40, now),
+        ];
+        assert!(settings.no_show_penalty(&old, now).is_none());
+
+        let recent = [
+            record(AttendanceStatus::NoShow, 3, now),
+            record(AttendanceStatus::NoShow, 20, now),
+        ];
+        let applied = settings.no_show_penalty(&recent, now).unwrap();
+        assert_eq!(applied.penalty, NoShowPenalty::WaitlistOnly);
+        assert_eq!(applied.no_shows, 2);
+        assert_eq!(applied.window_days, 30);
+    }
+
+    #[test]
+    fn excused_absences_never_count() {
+        let now = Utc::now();
+        let settings = with_policies(vec![policy(1, NoShowPenalty::WaitlistOnly)]);
+        let history = [
+            record(AttendanceStatus::Excused, 1, now),
+            record(AttendanceStatus::Excused, 2, now),
+        ];
+
+        assert!(settings.no_show_penalty(&history, now).is_none());
+    }
+
+    #[test]
+    fn most_severe_penalty_applies() {
+        let now = Utc::now();
+        let settings = with_policies(vec![
+            policy(1, NoShowPenalty::DelayedSignup { hours: 24 }),
+            policy(2, NoShowPenalty::WaitlistOnly),
+            policy(1, NoShowPenalty::DelayedSignup { hours: 48 }),
+        ]);
+
+        let once = [record(AttendanceStatus::NoShow, 1, now)];
+        assert_eq!(
+            settings.no_show_penalty(&once, now).unwrap().penalty,
+            NoShowPenalty::DelayedSignup { hours: 48 }
+        );
+
+        let twice = [
+            record(AttendanceStatus::NoShow, 1, now),
+            record(AttendanceStatus::NoShow, 2, now),
+        ];
+        assert_eq!(
+            settings.no_show_penalty(&twice, now).unwrap().penalty,
+            NoShowPenalty::WaitlistOnly
+        );
+    }
+
+    fn membership(status: MembershipStatus, expires_at: Option<DateTime<Utc>>) -> Membership {
+        Membership {
+            status,
+            expires_at,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn unpaid_members_keep_their_free_trials() {
+        let now = Utc::now();
+        let settings = ClubSettings::default();
+        let unpaid = membership(MembershipStatus::Pending, None);
+
+        // Only practices actually attended use up a trial
+        let history = [
+            record(AttendanceStatus::Present, 3, now),
+            record(AttendanceStatus::NoShow, 5, now),
+            record(AttendanceStatus::Excused, 7, now),
+        ];
+        assert!(settings.unpaid_restriction(&unpaid, &history, now).is_none());
+
+        let history = [
+            record(AttendanceStatus::Present, 3, now),
+            record(AttendanceStatus::Late, 5, now),
+        ];
+        assert_eq!(
+            settings.unpaid_restriction(&unpaid, &history, now),
+            Some(UnpaidSignup::Refused)
+        );
+    }
+
+    #[test]
+    fn paid_members_are_never_restricted() {
+        let now = Utc::now();
+        let settings = ClubSettings::default();
+        let history = [
+            record(AttendanceStatus::Present, 3, now),
+            record(AttendanceStatus::Present, 5, now),
+            record(AttendanceStatus::Present, 7, now),
+        ];
+
+        let paid = membership(MembershipStatus::Paid, Some(now + Duration::days(30)));
+        assert!(settings.unpaid_restriction(&paid, &history, now).is_none());
+
+        let lapsed = membership(MembershipStatus::Paid, Some(now - Duration::days(1)));
+        assert_eq!(
+            settings.unpaid_restriction(&lapsed, &history, now),
+            Some(UnpaidSignup::Refused)
+        );
+    }
+
+    #[test]
+    fn unpaid_restriction_follows_the_policy() {
+        let now = Utc::now();
+        let settings = ClubSettings {
+            membership: MembershipPolicy {
+                free_trial_practices: 1,
+                unpaid: UnpaidSignup::WaitlistOnly,
+            },
+            ..Default::default()
+        };
+        let history = [record(AttendanceStatus::Present, 3, now)];
+
+        assert_eq!(
+            settings.unpaid_restriction(&membership(MembershipStatus::Expired, None), &history, now),
+            Some(UnpaidSignup::WaitlistOnly)
+        );
+    }
+}
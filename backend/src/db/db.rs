@@ -1,17 +1,30 @@
 use chrono::{DateTime, Utc};
 use futures::TryStreamExt;
 use mongodb::{
-    bson::{doc, oid::ObjectId},
-    Client, Database,
+    bson::{doc, from_bson, oid::ObjectId, to_bson, Document},
+    error::{ErrorKind, WriteFailure},
+    options::{IndexOptions, ReturnDocument},
+    Client, Database, IndexModel,
 };
 use std::error::Error;
-use tracing::info;
+use tracing::{error, info};
 
 use crate::sheets::models::SheetMetaData;
 
-use super::{practice::Practice, user::User};
+use super::{
+    attendance::Attendance,
+    audit::{roster_changes, AuditEntry, RosterChange},
+    club::ClubSettings,
+    discord_relink::DiscordRelink,
+    guest::Guest,
+    practice::{Practice, PracticeType},
+    seat_offer::{SeatOffer, SeatOfferStatus},
+    series::PracticeSeries,
+    user::{SignupTier, User},
+};
 
 pub struct DB {
+    #[allow(dead_code)]
     client: Client,
     db: Database,
 }
@@ -37,7 +50,134 @@ impl DB {
         let db = client.database(&db_name);
 
         info!("Successfully connected to mongoDB, database: {}", &db_name);
-        Ok(Self { client, db })
+        let db = Self { client, db };
+        db.migrate_practices_to_boats().await?;
+        db.migrate_practice_signup_times().await?;
+        db.create_indexes().await.map_err(|e| {
+            format!(
+                "Failed to create indexes, check that no two members share a Discord account: {}",
+                e
+            )
+        })?;
+
+        Ok(db)
+    }
+
+    /// A Discord account can be linked to at most one member. Members that
+    /// haven't linked one are left out of the index. A member has at most
+    /// one attendance record per practice.
+    async fn create_indexes(&self) -> Result<(), Box<dyn Error>> {
+        let index = IndexModel::builder()
+            .keys(doc! {"discord_id": 1})
+            .options(
+                IndexOptions::builder()
+                    .name("discord_id_unique".to_string())
+                    .unique(true)
+                    .partial_filter_expression(doc! {"discord_id": {"$type": "string"}})
+                    .build(),
+            )
+            .build();
+
+        self.db
+            .collection::<Document>("users")
+            .create_index(index)
+            .await?;
+
+        let index = IndexModel::builder()
+            .keys(doc! {"practice_id": 1, "user_id": 1})
+            .options(
+                IndexOptions::builder()
+                    .name("attendance_unique".to_string())
+                    .unique(true)
+                    .build(),
+            )
+            .build();
+
+        self.db
+            .collection::<Document>("attendance")
+            .create_index(index)
+            .await?;
+        Ok(())
+    }
+
+    /// Moves the roster of practices stored before they had boats into a
+    /// single boat.
+    async fn migrate_practices_to_boats(&self) -> Result<(), Box<dyn Error>> {
+        let collection = self.db.collection::<Document>("practices");
+        let result = collection
+            .update_many(
+                doc! {"boats": {"$exists": false}},
+                vec![
+                    doc! {"$set": {"boats": [{
+                        "name": "Boat 1",
+                        "left_side": "$left_side",
+                        "right_side": "$right_side",
+                        "steerer": "$steerer",
+                        "drummer": "$drummer",
+                    }]}},
+                    doc! {"$unset": ["left_side", "right_side", "steerer", "drummer"]},
+                ],
+            )
+            .await?;
+
+        if result.modified_count > 0 {
+            info!("Moved {} practices to a single boat", result.modified_count);
+        }
+        Ok(())
+    }
+
+    /// Fills in the signup times of practices stored before each practice
+    /// kept its own, from the club's signup window for the practice's type.
+    async fn migrate_practice_signup_times(&self) -> Result<(), Box<dyn Error>> {
+        const FIELDS: [&str; 3] = ["signup_opens_at", "signup_closes_at", "waitlist_transfer_at"];
+
+        let collection = self.db.collection::<Document>("practices");
+        let settings = self.get_club_settings().await.map_err(|e| e.to_string())?;
+        let missing = FIELDS
+            .iter()
+            .map(|field| doc! {*field: {"$exists": false}})
+            .collect::<Vec<_>>();
+
+        let mut cursor = collection.find(doc! {"$or": missing}).await?;
+        let mut migrated = 0;
+        while let Some(practice) = cursor.try_next().await? {
+            let practice_id = practice.get_object_id("_id")?;
+            let start_time: DateTime<Utc> = from_bson(
+                practice
+                    .get("start_time")
+                    .cloned()
+                    .ok_or("Practice has no start time")?,
+            )?;
+            let practice_type: PracticeType = practice
+                .get("practice_type")
+                .cloned()
+                .map(from_bson)
+                .transpose()?
+                .unwrap_or_default();
+
+            let window = settings.signup_window(practice_type);
+            let times = [
+                start_time - window.opens_before(),
+                start_time - window.closes_before(),
+                start_time - window.waitlist_transfer_before(),
+            ];
+
+            let mut set = Document::new();
+            for (field, time) in FIELDS.iter().zip(times) {
+                if !practice.contains_key(field) {
+                    set.insert(*field, to_bson(&time)?);
+                }
+            }
+            collection
+                .update_one(doc! {"_id": practice_id}, doc! {"$set": set})
+                .await?;
+            migrated += 1;
+        }
+
+        if migrated > 0 {
+            info!("Filled in signup times of {} practices", migrated);
+        }
+        Ok(())
     }
 
     pub async fn create_user_from_sheet(&self, user: &User) -> Result<(), Box<dyn Error>> {
@@ -52,17 +192,21 @@ impl DB {
     }
 
     pub async fn update_user(&self, user: &User) -> Result<(), Box<dyn Error>> {
+        let user_id = user.id.ok_or("User has no ID")?;
         let collection = self.db.collection::<User>("users");
         collection
-            .replace_one(doc! { "email" : &user.email}, user)
+            .replace_one(doc! { "_id": user_id }, user)
             .await?;
         Ok(())
     }
 
-    pub async fn create_practice(&self, practice: &Practice) -> Result<(), Box<dyn Error>> {
+    pub async fn create_practice(&self, practice: &Practice) -> Result<ObjectId, Box<dyn Error>> {
         let collection = self.db.collection::<Practice>("practices");
-        collection.insert_one(practice).await?;
-        Ok(())
+        let result = collection.insert_one(practice).await?;
+        Ok(result
+            .inserted_id
+            .as_object_id()
+            .ok_or("Inserted practice has no ObjectId")?)
     }
 
     pub async fn get_practice(
@@ -78,19 +222,214 @@ impl DB {
         Ok(collection.find_one(doc! {"_id" : user_id}).await?)
     }
 
+    pub async fn get_users(&self, user_ids: &[ObjectId]) -> Result<Vec<User>, Box<dyn Error>> {
+        let collection = self.db.collection::<User>("users");
+        let mut cursor = collection.find(doc! {"_id": {"$in": user_ids}}).await?;
+
+        let mut users = Vec::new();
+        while let Some(user) = cursor.try_next().await? {
+            users.push(user);
+        }
+
+        Ok(users)
+    }
+
     pub async fn get_user_by_discord_id(&self, discord_id: &str) -> Result<Option<User>, Box<dyn Error>> {
         let collection = self.db.collection::<User>("users");
         Ok(collection.find_one(doc! {"discord_id" : discord_id}).await?)
     }
 
-    pub async fn update_practice(&self, practice: &Practice) -> Result<(), Box<dyn Error + Send + Sync>> {
-        let collection = self.db.collection::<Practice>("practices");
+    /// Links a member to a Discord account, or unlinks them with `None`.
+    pub async fn set_discord_id(&self, user_id: ObjectId, discord_id: Option<&str>) -> Result<(), Box<dyn Error>> {
+        let collection = self.db.collection::<User>("users");
+        let result = collection
+            .update_one(doc! {"_id": user_id}, doc! {"$set": {"discord_id": discord_id}})
+            .await;
+
+        match result {
+            Err(e) if matches!(
+                e.kind.as_ref(),
+                ErrorKind::Write(WriteFailure::WriteError(write)) if write.code == 11000
+            ) =>
+            {
+                Err("That Discord account is already linked to another member".into())
+            }
+            Err(e) => Err(e.into()),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Starts a relink, replacing any the member already had going.
+    pub async fn save_discord_relink(&self, relink: &DiscordRelink) -> Result<(), Box<dyn Error>> {
+        let collection = self.db.collection::<DiscordRelink>("discord_relinks");
         collection
-            .replace_one(doc! {"_id" : practice.id.unwrap()}, practice)
+            .replace_one(doc! {"_id": relink.user_id}, relink)
+            .upsert(true)
             .await?;
         Ok(())
     }
 
+    pub async fn get_discord_relink(&self, user_id: ObjectId) -> Result<Option<DiscordRelink>, Box<dyn Error>> {
+        let collection = self.db.collection::<DiscordRelink>("discord_relinks");
+        Ok(collection.find_one(doc! {"_id": user_id}).await?)
+    }
+
+    pub async fn delete_discord_relink(&self, user_id: ObjectId) -> Result<(), Box<dyn Error>> {
+        let collection = self.db.collection::<DiscordRelink>("discord_relinks");
+        collection.delete_one(doc! {"_id": user_id}).await?;
+        Ok(())
+    }
+
+    pub async fn get_execs(&self) -> Result<Vec<User>, Box<dyn Error + Send + Sync>> {
+        let collection = self.db.collection::<User>("users");
+        let mut cursor = collection
+            .find(doc! {"user_type": {"$in": ["Exec", "Admin"]}})
+            .await?;
+
+        let mut users = Vec::new();
+        while let Some(user) = cursor.try_next().await? {
+            users.push(user);
+        }
+
+        Ok(users)
+    }
+
+    /// Members of a signup tier: execs, or the competitive roster.
+    pub async fn get_tier_members(
+        &self,
+        tier: SignupTier,
+    ) -> Result<Vec<User>, Box<dyn Error + Send + Sync>> {
+        let filter = match tier {
+            SignupTier::Exec => doc! {"user_type": {"$in": ["Exec", "Admin"]}},
+            SignupTier::Competitive => doc! {"competitive_roster": true},
+        };
+
+        let collection = self.db.collection::<User>("users");
+        let mut cursor = collection.find(filter).await?;
+
+        let mut users = Vec::new();
+        while let Some(user) = cursor.try_next().await? {
+            users.push(user);
+        }
+
+        Ok(users)
+    }
+
+    /// Sets some of a practice's details without touching its roster, then
+    /// reloads `practice` as stored. The revision is bumped so roster writes
+    /// based on an earlier read start over instead of undoing the change.
+    pub async fn update_practice_fields(
+        &self,
+        practice: &mut Practice,
+        fields: Document,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let collection = self.db.collection::<Practice>("practices");
+        let practice_id = practice.id.ok_or("Practice has no ID")?;
+
+        *practice = collection
+            .find_one_and_update(
+                doc! {"_id": practice_id},
+                doc! {"$set": fields, "$inc": {"revision": 1}},
+            )
+            .return_document(ReturnDocument::After)
+            .await?
+            .ok_or("Practice not found")?;
+        Ok(())
+    }
+
+    /// Writes the times of a rescheduled practice.
+    pub async fn update_practice_schedule(
+        &self,
+        practice: &mut Practice,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut fields = doc! {
+            "date": to_bson(&practice.date)?,
+            "start_time": to_bson(&practice.start_time)?,
+            "end_time": to_bson(&practice.end_time)?,
+            "signup_opens_at": to_bson(&practice.signup_opens_at)?,
+            "signup_closes_at": to_bson(&practice.signup_closes_at)?,
+            "waitlist_transfer_at": to_bson(&practice.waitlist_transfer_at)?,
+        };
+        if let Some(lottery) = &practice.lottery {
+            fields.insert("lottery.draws_at", to_bson(&lottery.draws_at)?);
+        }
+
+        self.update_practice_fields(practice, fields).await
+    }
+
+    /// Writes a practice whose roster changed, recording each member's move
+    /// in the audit log. Nothing is written if someone else wrote the
+    /// practice since it was read; the caller reads it again and retries.
+    /// Returns whether it was written, and bumps the revision if so.
+    pub async fn update_roster(
+        &self,
+        practice: &mut Practice,
+        change: &RosterChange,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let collection = self.db.collection::<Practice>("practices");
+        let practice_id = practice.id.ok_or("Practice has no ID")?;
+
+        // Practices stored before revisions existed have no revision field
+        let filter = if practice.revision == 0 {
+            doc! {"_id": practice_id, "revision": {"$in": [0, null]}}
+        } else {
+            doc! {"_id": practice_id, "revision": practice.revision}
+        };
+
+        practice.revision += 1;
+        let before = collection.find_one_and_replace(filter, &*practice).await?;
+
+        match before {
+            Some(before) => {
+                self.audit_roster(Some(&before), practice, change).await;
+                Ok(true)
+            }
+            None => {
+                practice.revision -= 1;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Appends the roster differences between two versions of a practice to
+    /// the audit log. The roster is already written by then, so a failure is
+    /// logged rather than failing the change.
+    pub async fn audit_roster(&self, before: Option<&Practice>, after: &Practice, change: &RosterChange) {
+        let entries = roster_changes(before, after, change);
+        if entries.is_empty() {
+            return;
+        }
+
+        let collection = self.db.collection::<AuditEntry>("audit_log");
+        if let Err(e) = collection.insert_many(&entries).await {
+            error!("Failed to audit {} roster changes on practice {:?}: {}", entries.len(), after.id, e);
+        }
+    }
+
+    pub async fn get_practice_audit(
+        &self,
+        practice_id: ObjectId,
+    ) -> Result<Vec<AuditEntry>, Box<dyn Error>> {
+        self.get_audit(doc! {"practice_id": practice_id}).await
+    }
+
+    pub async fn get_user_audit(&self, user_id: ObjectId) -> Result<Vec<AuditEntry>, Box<dyn Error>> {
+        self.get_audit(doc! {"user_id": user_id}).await
+    }
+
+    /// Audit entries matching a filter, oldest first.
+    async fn get_audit(&self, filter: Document) -> Result<Vec<AuditEntry>, Box<dyn Error>> {
+        let collection = self.db.collection::<AuditEntry>("audit_log");
+        let mut cursor = collection.find(filter).sort(doc! {"at": 1}).await?;
+
+        let mut entries = Vec::new();
+        while let Some(entry) = cursor.try_next().await? {
+            entries.push(entry);
+        }
+
+        Ok(entries)
+    }
+
     pub async fn get_sheet_metadata(
         &self,
         sheet_id: &str,
@@ -126,6 +465,7 @@ impl DB {
         Ok(())
     }
 
+    #[allow(dead_code)]
     pub async fn get_practices_opening_soon(
         &self,
     ) -> Result<Vec<Practice>, Box<dyn Error + Send + Sync>> {
@@ -162,21 +502,171 @@ impl DB {
     ) -> Result<Option<Practice>, Box<dyn Error>> {
         let collection = self.db.collection::<Practice>("practices");
         Ok(collection
-            .find_one(doc! {"date": date.to_rfc3339()})
+            .find_one(doc! {"date": to_bson(&date)?})
             .await?)
     }
 
-    pub async fn get_previous_practice(
-      &self,
-      curr_practice: &Practice
+    /// Practices whose waitlist carries over into the given one, oldest first.
+    pub async fn get_waitlist_sources(
+        &self,
+        practice: &Practice,
+    ) -> Result<Vec<Practice>, Box<dyn Error + Send + Sync>> {
+        let collection = self.db.collection::<Practice>("practices");
+        let practice_id = practice.id.ok_or("Practice has no ID")?;
+
+        let mut cursor = collection
+            .find(doc! {"waitlist_feeds_into": practice_id})
+            .sort(doc! {"start_time": 1})
+            .await?;
+
+        let mut practices = Vec::new();
+        while let Some(practice) = cursor.try_next().await? {
+            practices.push(practice);
+        }
+
+        Ok(practices)
+    }
+
+    /// Closest non-cancelled practice of the same type (and series, if any)
+    /// before or after the given one.
+    async fn get_adjacent_practice(
+        &self,
+        practice: &Practice,
+        before: bool,
     ) -> Result<Option<Practice>, Box<dyn Error + Send + Sync>> {
-      let collection = self.db.collection::<Practice>("practices");
-      let previous_practice_time = curr_practice.start_time - chrono::Duration::weeks(1);
+        let collection = self.db.collection::<Practice>("practices");
+        let practice_id = practice.id.ok_or("Practice has no ID")?;
+        let start_time = to_bson(&practice.start_time)?;
+
+        let mut filter = doc! {
+            "_id": {"$ne": practice_id},
+            "practice_type": to_bson(&practice.practice_type)?,
+            "cancellation": {"$exists": false},
+            "start_time": if before { doc! {"$lt": start_time} } else { doc! {"$gt": start_time} },
+        };
+        if let Some(series_id) = practice.series_id {
+            filter.insert("series_id", series_id);
+        }
+
+        Ok(collection
+            .find_one(filter)
+            .sort(doc! {"start_time": if before { -1 } else { 1 }})
+            .await?)
+    }
+
+    /// Derives waitlist carry-over links for a newly created practice: the
+    /// previous practice of the same slot feeds into it, and it feeds into the
+    /// next one. Links set to a later practice are moved; earlier ones are kept.
+    pub async fn link_waitlist_feed(
+        &self,
+        practice: &mut Practice,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if !practice.practice_type.carries_over_waitlist() {
+            return Ok(());
+        }
+
+        let practice_id = practice.id.ok_or("Practice has no ID")?;
+
+        if let Some(mut previous) = self.get_adjacent_practice(practice, true).await? {
+            let relink = match previous.waitlist_feeds_into {
+                Some(target_id) => match self.get_practice(target_id).await? {
+                    Some(target) => target.start_time > practice.start_time,
+                    None => true,
+                },
+                None => true,
+            };
+
+            if relink {
+                info!("Linking waitlist of practice {:?} into {}", previous.id, practice_id);
+                self.set_waitlist_feed(&mut previous, Some(practice_id)).await?;
+            }
+        }
+
+        if let Some(next) = self.get_adjacent_practice(practice, false).await? {
+            self.set_waitlist_feed(practice, next.id).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Redoes the carry-over links of a practice that moved: waitlists that
+    /// fed into it skip ahead to its old target, then it's linked as if new.
+    pub async fn relink_waitlist_feed(
+        &self,
+        practice: &mut Practice,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        for mut source in self.get_waitlist_sources(practice).await? {
+            self.set_waitlist_feed(&mut source, practice.waitlist_feeds_into)
+                .await?;
+        }
+
+        self.set_waitlist_feed(practice, None).await?;
+
+        self.link_waitlist_feed(practice).await
+    }
 
-      Ok(collection
-        .find_one(doc!{"start_time" : previous_practice_time.to_rfc3339()})
-        .await?)
+    pub async fn set_waitlist_feed(
+        &self,
+        practice: &mut Practice,
+        feeds_into: Option<ObjectId>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.update_practice_fields(practice, doc! {"waitlist_feeds_into": feeds_into})
+            .await
     }
+
+    /// Practices starting since `since` that a member is on in any list,
+    /// oldest first.
+    pub async fn get_member_practices(
+        &self,
+        user_id: ObjectId,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<Practice>, Box<dyn Error>> {
+        let collection = self.db.collection::<Practice>("practices");
+        let mut cursor = collection
+            .find(doc! {
+                "start_time": {"$gte": to_bson(&since)?},
+                "$or": [
+                    {"boats.left_side": user_id},
+                    {"boats.right_side": user_id},
+                    {"boats.steerer": user_id},
+                    {"boats.drummer": user_id},
+                    {"left_side_waitlist": user_id},
+                    {"right_side_waitlist": user_id},
+                    {"lottery.entries.user_id": user_id},
+                ],
+            })
+            .sort(doc! {"start_time": 1})
+            .await?;
+
+        let mut practices = Vec::new();
+        while let Some(practice) = cursor.try_next().await? {
+            practices.push(practice);
+        }
+
+        Ok(practices)
+    }
+
+    /// Practices starting since `since` whose lottery has been drawn.
+    pub async fn get_drawn_lotteries_since(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<Practice>, Box<dyn Error + Send + Sync>> {
+        let collection = self.db.collection::<Practice>("practices");
+        let mut cursor = collection
+            .find(doc! {
+                "lottery.draw": {"$exists": true},
+                "start_time": {"$gte": to_bson(&since)?},
+            })
+            .await?;
+
+        let mut practices = Vec::new();
+        while let Some(practice) = cursor.try_next().await? {
+            practices.push(practice);
+        }
+
+        Ok(practices)
+    }
+
     pub async fn get_all_practices(&self) -> Result<Vec<Practice>, Box<dyn Error>> {
         let collection = self.db.collection::<Practice>("practices");
         let mut cursor = collection.find(doc!{}).await?;
@@ -188,4 +678,196 @@ impl DB {
 
         Ok(practices)
     }
+
+    pub async fn create_series(&self, series: &PracticeSeries) -> Result<ObjectId, Box<dyn Error>> {
+        let collection = self.db.collection::<PracticeSeries>("practice_series");
+        let result = collection.insert_one(series).await?;
+        Ok(result
+            .inserted_id
+            .as_object_id()
+            .ok_or("Inserted series has no ObjectId")?)
+    }
+
+    pub async fn get_series(
+        &self,
+        series_id: ObjectId,
+    ) -> Result<Option<PracticeSeries>, Box<dyn Error>> {
+        let collection = self.db.collection::<PracticeSeries>("practice_series");
+        Ok(collection.find_one(doc! {"_id": series_id}).await?)
+    }
+
+    pub async fn update_series(&self, series: &PracticeSeries) -> Result<(), Box<dyn Error>> {
+        let collection = self.db.collection::<PracticeSeries>("practice_series");
+        collection
+            .replace_one(doc! {"_id": series.id.ok_or("Series has no ID")?}, series)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_series_practices(
+        &self,
+        series_id: ObjectId,
+    ) -> Result<Vec<Practice>, Box<dyn Error>> {
+        let collection = self.db.collection::<Practice>("practices");
+        let mut cursor = collection.find(doc! {"series_id": series_id}).await?;
+
+        let mut practices = Vec::new();
+        while let Some(practice) = cursor.try_next().await? {
+            practices.push(practice);
+        }
+
+        Ok(practices)
+    }
+
+    pub async fn get_club_settings(&self) -> Result<ClubSettings, Box<dyn Error + Send + Sync>> {
+        let collection = self.db.collection::<ClubSettings>("club_settings");
+        Ok(collection.find_one(doc! {}).await?.unwrap_or_default())
+    }
+
+    pub async fn update_club_settings(
+        &self,
+        settings: &ClubSettings,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let collection = self.db.collection::<ClubSettings>("club_settings");
+        collection.replace_one(doc! {}, settings).upsert(true).await?;
+        Ok(())
+    }
+
+    /// Records attendance, replacing any earlier record for the same member
+    /// and practice.
+    pub async fn record_attendance(&self, attendance: &Attendance) -> Result<(), Box<dyn Error>> {
+        let collection = self.db.collection::<Attendance>("attendance");
+        collection
+            .replace_one(
+                doc! {"practice_id": attendance.practice_id, "user_id": attendance.user_id},
+                attendance,
+            )
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_practice_attendance(
+        &self,
+        practice_id: ObjectId,
+    ) -> Result<Vec<Attendance>, Box<dyn Error>> {
+        let collection = self.db.collection::<Attendance>("attendance");
+        let mut cursor = collection.find(doc! {"practice_id": practice_id}).await?;
+
+        let mut records = Vec::new();
+        while let Some(record) = cursor.try_next().await? {
+            records.push(record);
+        }
+
+        Ok(records)
+    }
+
+    /// A member's attendance history, most recent practice first.
+    pub async fn get_user_attendance(
+        &self,
+        user_id: ObjectId,
+    ) -> Result<Vec<Attendance>, Box<dyn Error>> {
+        let collection = self.db.collection::<Attendance>("attendance");
+        let mut cursor = collection
+            .find(doc! {"user_id": user_id})
+            .sort(doc! {"practice_start": -1})
+            .await?;
+
+        let mut records = Vec::new();
+        while let Some(record) = cursor.try_next().await? {
+            records.push(record);
+        }
+
+        Ok(records)
+    }
+
+    pub async fn create_seat_offer(&self, offer: &SeatOffer) -> Result<ObjectId, Box<dyn Error>> {
+        let collection = self.db.collection::<SeatOffer>("seat_offers");
+        let result = collection.insert_one(offer).await?;
+        Ok(result
+            .inserted_id
+            .as_object_id()
+            .ok_or("Inserted seat offer has no ObjectId")?)
+    }
+
+    pub async fn get_seat_offer(&self, offer_id: ObjectId) -> Result<Option<SeatOffer>, Box<dyn Error>> {
+        let collection = self.db.collection::<SeatOffer>("seat_offers");
+        Ok(collection.find_one(doc! {"_id": offer_id}).await?)
+    }
+
+    /// Offers to or from a member that are still waiting on an answer.
+    pub async fn get_pending_seat_offers(&self, user_id: ObjectId) -> Result<Vec<SeatOffer>, Box<dyn Error>> {
+        let collection = self.db.collection::<SeatOffer>("seat_offers");
+        let mut cursor = collection
+            .find(doc! {
+                "status": to_bson(&SeatOfferStatus::Pending)?,
+                "$or": [{"from_user": user_id}, {"to_user": user_id}],
+            })
+            .await?;
+
+        let mut offers = Vec::new();
+        while let Some(offer) = cursor.try_next().await? {
+            offers.push(offer);
+        }
+
+        Ok(offers)
+    }
+
+    pub async fn get_seat_offers_by_status(
+        &self,
+        status: SeatOfferStatus,
+    ) -> Result<Vec<SeatOffer>, Box<dyn Error>> {
+        let collection = self.db.collection::<SeatOffer>("seat_offers");
+        let mut cursor = collection.find(doc! {"status": to_bson(&status)?}).await?;
+
+        let mut offers = Vec::new();
+        while let Some(offer) = cursor.try_next().await? {
+            offers.push(offer);
+        }
+
+        Ok(offers)
+    }
+
+    /// Writes the offer only if it is still in the `expected` status, returning
+    /// false when another request moved it on first.
+    pub async fn update_seat_offer(
+        &self,
+        offer: &SeatOffer,
+        expected: SeatOfferStatus,
+    ) -> Result<bool, Box<dyn Error>> {
+        let collection = self.db.collection::<SeatOffer>("seat_offers");
+        let filter = doc! {
+            "_id": offer.id.ok_or("Seat offer has no ID")?,
+            "status": to_bson(&expected)?,
+        };
+        let result = collection.replace_one(filter, offer).await?;
+        Ok(result.matched_count == 1)
+    }
+
+    pub async fn create_guest(&self, guest: &Guest) -> Result<ObjectId, Box<dyn Error>> {
+        let collection = self.db.collection::<Guest>("guests");
+        let result = collection.insert_one(guest).await?;
+        Ok(result
+            .inserted_id
+            .as_object_id()
+            .ok_or("Inserted guest has no ObjectId")?)
+    }
+
+    pub async fn get_guest(&self, guest_id: ObjectId) -> Result<Option<Guest>, Box<dyn Error + Send + Sync>> {
+        let collection = self.db.collection::<Guest>("guests");
+        Ok(collection.find_one(doc! {"_id": guest_id}).await?)
+    }
+
+    pub async fn get_guest_by_email(&self, email: &str) -> Result<Option<Guest>, Box<dyn Error>> {
+        let collection = self.db.collection::<Guest>("guests");
+        Ok(collection.find_one(doc! {"email": email}).await?)
+    }
+
+    pub async fn update_guest(&self, guest: &Guest) -> Result<(), Box<dyn Error>> {
+        let collection = self.db.collection::<Guest>("guests");
+        collection
+            .replace_one(doc! {"_id": guest.id.ok_or("Guest has no ID")?}, guest)
+            .await?;
+        Ok(())
+    }
 }
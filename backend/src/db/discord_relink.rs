@@ -0,0 +1,47 @@
+use chrono::{DateTime, Duration, Utc};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+/// How long an emailed code can be used for.
+pub const CODE_VALID_MINUTES: i64 = 15;
+
+/// Wrong codes allowed before the member has to ask for a new one.
+pub const MAX_ATTEMPTS: u32 = 5;
+
+/// A member moving their account to another Discord account, confirmed with
+/// a code sent to their email. Kept apart from the member so the code never
+/// shows up in API responses.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DiscordRelink {
+    #[serde(rename = "_id")]
+    pub user_id: ObjectId,
+    pub discord_id: String,
+    pub code: String,
+    #[serde(default)]
+    pub attempts: u32,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl DiscordRelink {
+    pub fn new(user_id: ObjectId, discord_id: String) -> Self {
+        let now = Utc::now();
+
+        Self {
+            user_id,
+            discord_id,
+            code: format!("{:06}", RandomState::new().build_hasher().finish() % 1_000_000),
+            attempts: 0,
+            created_at: now,
+            expires_at: now + Duration::minutes(CODE_VALID_MINUTES),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        Utc::now() >= self.expires_at
+    }
+}
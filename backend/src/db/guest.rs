@@ -0,0 +1,30 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use super::user::Side;
+
+/// Someone trying out the club before registering. Guests are seated under
+/// their own ID, which becomes their user ID if they register with the same
+/// email, so their seats carry over.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Guest {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub first_name: String,
+    pub last_name: String,
+    pub email: String,
+    pub side: Side,
+    pub waiver_signed: bool,
+    /// The member who first brought them.
+    pub invited_by: ObjectId,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub converted_at: Option<DateTime<Utc>>,
+}
+
+impl Guest {
+    pub fn is_converted(&self) -> bool {
+        self.converted_at.is_some()
+    }
+}
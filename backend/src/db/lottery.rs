@@ -0,0 +1,242 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+use super::user::{Experience, Side};
+
+/// How far back weighted draws look at attendance and past draws.
+pub const WEIGHTING_WINDOW_DAYS: i64 = 30;
+
+/// What, besides luck, decides a draw.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LotteryWeighting {
+    /// Every entry has the same chance.
+    #[default]
+    Equal,
+    /// Members who showed up to more practices lately get better odds.
+    RecentAttendance,
+    /// Members who didn't get a seat in recent draws get better odds.
+    MissedPractices,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LotteryEntry {
+    pub user_id: ObjectId,
+    /// The member's preferred side; `NA` entrants may be seated on either.
+    pub side: Side,
+    #[serde(default)]
+    pub experience: Option<Experience>,
+    /// Penalized members are only drawn for waitlist spots.
+    #[serde(default)]
+    pub waitlist_only: bool,
+    pub entered_at: DateTime<Utc>,
+}
+
+/// The outcome of a draw, with everything needed to re-run it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LotteryDraw {
+    pub seed: i64,
+    pub drawn_at: DateTime<Utc>,
+    /// The weight each entry was drawn with, in entry order.
+    pub weights: Vec<f64>,
+    /// Entrants in the order they were drawn.
+    pub order: Vec<ObjectId>,
+    pub seated: Vec<ObjectId>,
+    pub waitlisted: Vec<ObjectId>,
+    /// Not drawn for a seat and the waitlist was full.
+    pub dropped: Vec<ObjectId>,
+}
+
+/// Signup requests collected until `draws_at`, when a seeded draw fills the
+/// seats. Signups after the draw are first come, first served again.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Lottery {
+    pub draws_at: DateTime<Utc>,
+    #[serde(default)]
+    pub weighting: LotteryWeighting,
+    #[serde(default)]
+    pub entries: Vec<LotteryEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub draw: Option<LotteryDraw>,
+}
+
+impl Lottery {
+    pub fn new(draws_at: DateTime<Utc>, weighting: LotteryWeighting) -> Self {
+        Self {
+            draws_at,
+            weighting,
+            entries: Vec::new(),
+            draw: None,
+        }
+    }
+
+    /// Entries are taken until the draw has actually happened.
+    pub fn is_open(&self) -> bool {
+        self.draw.is_none()
+    }
+
+    pub fn new_seed() -> i64 {
+        RandomState::new().build_hasher().finish() as i64
+    }
+
+    /// Orders the entries by weighted sampling without replacement
+    /// (Efraimidis-Spirakis). The same entries, weights and seed always give
+    /// the same order, so any draw can be checked after the fact.
+    pub fn draw_order(&self, weights: &[f64], seed: i64) -> Vec<ObjectId> {
+        let mut rng = SplitMix64(seed as u64);
+
+        let mut keyed = self
+            .entries
+            .iter()
+            .zip(weights)
+            .map(|(entry, weight)| (rng.next_f64().powf(1.0 / weight), entry.user_id))
+            .collect::<Vec<_>>();
+        keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        keyed.into_iter().map(|(_, user_id)| user_id).collect()
+    }
+}
+
+/// A small, well-known PRNG, so draws don't depend on a library's algorithm
+/// staying the same between versions.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `(0, 1]`.
+    fn next_f64(&mut self) -> f64 {
+        ((self.next_u64() >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::practice::{Practice, PracticeType};
+    use chrono::Duration;
+
+    fn entry(side: Side, waitlist_only: bool) -> LotteryEntry {
+        LotteryEntry {
+            user_id: ObjectId::new(),
+            side,
+            experience: None,
+            waitlist_only,
+            entered_at: Utc::now(),
+        }
+    }
+
+    fn lottery_with(entries: Vec<LotteryEntry>) -> Lottery {
+        let mut lottery = Lottery::new(Utc::now(), LotteryWeighting::Equal);
+        lottery.entries = entries;
+        lottery
+    }
+
+    fn pool_practice(entries: Vec<LotteryEntry>) -> Practice {
+        let start = Utc::now() + Duration::days(1);
+        let mut practice = Practice::new(
+            PracticeType::Pool,
+            start,
+            start,
+            start + Duration::hours(1),
+            &PracticeType::Pool.default_signup_window(),
+        )
+        .unwrap();
+        practice.lottery = Some(lottery_with(entries));
+        practice
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_order() {
+        let lottery = lottery_with((0..10).map(|_| entry(Side::Left, false)).collect());
+        let weights = vec![1.0; 10];
+
+        assert_eq!(lottery.draw_order(&weights, 42), lottery.draw_order(&weights, 42));
+        assert_ne!(lottery.draw_order(&weights, 42), lottery.draw_order(&weights, 43));
+    }
+
+    #[test]
+    fn order_holds_every_entry_once() {
+        let lottery = lottery_with((0..10).map(|_| entry(Side::Left, false)).collect());
+        let mut order = lottery.draw_order(&[1.0; 10], 7);
+        let mut entrants = lottery
+            .entries
+            .iter()
+            .map(|entry| entry.user_id)
+            .collect::<Vec<_>>();
+
+        order.sort();
+        entrants.sort();
+        assert_eq!(order, entrants);
+    }
+
+    #[test]
+    fn heavier_weights_are_drawn_first_more_often() {
+        let lottery = lottery_with((0..10).map(|_| entry(Side::Left, false)).collect());
+        let favourite = lottery.entries[0].user_id;
+        let mut weights = vec![1.0; 10];
+        weights[0] = 1000.0;
+
+        let first = (0..100)
+            .filter(|seed| lottery.draw_order(&weights, *seed)[0] == favourite)
+            .count();
+        assert!(first >= 90, "favourite drawn first {} times out of 100", first);
+    }
+
+    #[test]
+    fn draw_fills_seats_then_waitlist_then_drops() {
+        let (seats, waitlist) = PracticeType::Pool.default_capacity();
+        let entrants = seats + waitlist + 3;
+        let mut practice = pool_practice((0..entrants).map(|_| entry(Side::Left, false)).collect());
+
+        let draw = practice.run_lottery(vec![1.0; entrants], 1).unwrap().clone();
+
+        assert_eq!(draw.seated.len(), seats);
+        assert_eq!(draw.waitlisted.len(), waitlist);
+        assert_eq!(draw.dropped.len(), 3);
+        assert_eq!(draw.seated, draw.order[..seats]);
+        assert!(!practice.lottery_is_open());
+        assert!(practice.run_lottery(vec![1.0; entrants], 1).is_none());
+    }
+
+    #[test]
+    fn waitlist_only_entries_are_never_seated() {
+        let penalized = entry(Side::Left, true);
+        let penalized_id = penalized.user_id;
+        let mut practice = pool_practice(vec![penalized, entry(Side::Left, false)]);
+
+        let draw = practice.run_lottery(vec![1.0; 2], 3).unwrap();
+
+        assert_eq!(draw.waitlisted, vec![penalized_id]);
+        assert_eq!(draw.seated.len(), 1);
+    }
+
+    #[test]
+    fn redrawing_with_the_recorded_seed_gives_the_same_result() {
+        let entries = (0..12)
+            .map(|i| entry(if i % 3 == 0 { Side::NA } else { Side::Right }, false))
+            .collect::<Vec<_>>();
+        let mut practice = pool_practice(entries.clone());
+        let mut again = pool_practice(entries);
+
+        let weights = (1..=12).map(f64::from).collect::<Vec<_>>();
+        let draw = practice.run_lottery(weights, Lottery::new_seed()).unwrap().clone();
+        let redraw = again.run_lottery(draw.weights.clone(), draw.seed).unwrap();
+
+        assert_eq!(draw.order, redraw.order);
+        assert_eq!(draw.seated, redraw.seated);
+        assert_eq!(draw.waitlisted, redraw.waitlisted);
+        assert_eq!(practice.boats[0].left_side, again.boats[0].left_side);
+        assert_eq!(practice.boats[0].right_side, again.boats[0].right_side);
+    }
+}
@@ -1,3 +1,12 @@
+#[allow(clippy::module_inception)]
 pub (crate) mod db;
 pub (crate) mod user;
 pub (crate) mod practice;
+pub (crate) mod series;
+pub (crate) mod club;
+pub (crate) mod attendance;
+pub (crate) mod lottery;
+pub (crate) mod seat_offer;
+pub (crate) mod guest;
+pub (crate) mod audit;
+pub (crate) mod discord_relink;
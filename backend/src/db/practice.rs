@@ -1,20 +1,53 @@
-use crate::DB;
-use std::sync::Arc;
 
 use crate::sheets::models::PracticeSheetData;
 
-use super::user::{Side, UserType};
-use chrono::{DateTime, Utc};
+use super::club::{EarlyAccess, GuestPolicy, SignupWindow};
+use super::guest::Guest;
+use super::lottery::{Lottery, LotteryDraw, LotteryEntry};
+use super::user::{BoatRole, Experience, Side, SignupTier, User};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use chrono_tz::America::New_York;
 use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum PracticeError {
     #[error("Practice is locked")]
     Locked,
+    #[error("Practice has been cancelled")]
+    Cancelled,
+    #[error("Practice end time must be after start time")]
+    InvalidTimes,
+    #[error("Signups must open before they close, and waitlists transfer before signups open")]
+    InvalidSignupWindow,
     #[error("Practice and waitlist are full")]
     Full,
+    #[error("Already signed up for this practice")]
+    AlreadySignedUp,
+    #[error("This practice has no {0:?} seat")]
+    NoRoleSeat(BoatRole),
+    #[error("The {0:?} seat is already taken")]
+    RoleTaken(BoatRole),
+    #[error("Not qualified to sign up as {0:?}")]
+    NotQualified(BoatRole),
+    #[error("Can't paddle on the {0:?} side")]
+    WrongSide(Side),
+    #[error("Not seated on this practice")]
+    NotSeated,
+    #[error("This practice already has as many guests as allowed")]
+    GuestLimit,
+    #[error("You've already brought as many guests as allowed")]
+    HostGuestLimit,
+    #[error("Guests can be added once the lottery has been drawn")]
+    LotteryOpen,
+    #[error("This practice has no such spot")]
+    NoSuchSpot,
+    #[error("That spot is already taken")]
+    SpotTaken,
+    #[error("The new order must list everyone on the waitlist exactly once")]
+    WaitlistMismatch,
     #[error("User not found")]
     UserNotFound,
     #[error("User has no ID")]
@@ -23,203 +56,1261 @@ pub enum PracticeError {
     DatabaseError(String),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The kind of session a practice is. Drives the roster shape and signup rules.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PracticeType {
+    #[default]
+    Water,
+    Pool,
+    Fitness,
+    RaceDay,
+    TimeTrial,
+}
+
+impl PracticeType {
+    /// Returns `(seats, waitlist spots)` per side. Side-less sessions put
+    /// everyone on a single list, so the seat count is the total.
+    pub fn default_capacity(&self) -> (usize, usize) {
+        match self {
+            PracticeType::Water => (17, 6),
+            PracticeType::Pool => (8, 2),
+            PracticeType::Fitness => (30, 10),
+            PracticeType::RaceDay => (10, 2),
+            PracticeType::TimeTrial => (10, 4),
+        }
+    }
+
+    pub fn has_sides(&self) -> bool {
+        !matches!(self, PracticeType::Fitness)
+    }
+
+    /// How long a session runs when no end time is given.
+    pub fn default_duration(&self) -> Duration {
+        match self {
+            PracticeType::Water | PracticeType::TimeTrial => Duration::hours(2),
+            PracticeType::Pool | PracticeType::Fitness => Duration::hours(1),
+            PracticeType::RaceDay => Duration::hours(8),
+        }
+    }
+
+    /// The signup window used when the club hasn't configured one for this type.
+    pub fn default_signup_window(&self) -> SignupWindow {
+        let opens_minutes_before = match self {
+            PracticeType::Water | PracticeType::Pool | PracticeType::Fitness => 60,
+            PracticeType::TimeTrial => 24 * 60,
+            PracticeType::RaceDay => 7 * 24 * 60,
+        };
+
+        SignupWindow {
+            opens_minutes_before,
+            closes_minutes_before: 0,
+            waitlist_transfer_minutes_before: opens_minutes_before + 1,
+        }
+    }
+
+    /// Whether the session is in a boat that needs a steerer and drummer.
+    pub fn has_boat_roles(&self) -> bool {
+        matches!(
+            self,
+            PracticeType::Water | PracticeType::RaceDay | PracticeType::TimeTrial
+        )
+    }
+
+    /// Whether the waitlist of the previous session gets priority seats in this one.
+    pub fn carries_over_waitlist(&self) -> bool {
+        matches!(self, PracticeType::Water | PracticeType::Pool)
+    }
+}
+
+/// Outcome of carrying a previous practice's waitlist over.
+#[derive(Debug, Default, Serialize)]
+pub struct WaitlistTransferReport {
+    /// Given a seat on the main list.
+    pub placed: Vec<ObjectId>,
+    /// No free seat, so moved to the head of the waitlist.
+    pub waitlisted: Vec<ObjectId>,
+    /// Already signed up for this practice.
+    pub duplicates: Vec<ObjectId>,
+    /// No free seat and the waitlist is full.
+    pub dropped: Vec<ObjectId>,
+}
+
+impl WaitlistTransferReport {
+    pub fn summary(&self) -> String {
+        format!(
+            "{} seated, {} moved to the head of the waitlist, {} already signed up, {} dropped (waitlist full)",
+            self.placed.len(),
+            self.waitlisted.len(),
+            self.duplicates.len(),
+            self.dropped.len()
+        )
+    }
+}
+
+/// Outcome of rebalancing flex paddlers at lock time.
+#[derive(Debug, Default, Serialize)]
+pub struct RebalanceReport {
+    /// Flex paddlers that changed side.
+    pub moved: Vec<ObjectId>,
+    /// Waitlisted paddlers that got the seat a flex paddler left.
+    pub promoted: Vec<ObjectId>,
+}
+
+/// Pins a paddler to a row (1 at the front) and side of one of the boats.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LineupOverride {
+    pub user_id: ObjectId,
+    #[serde(default)]
+    pub boat: usize,
+    pub row: usize,
+    pub side: Side,
+}
+
+/// How signups are spread over the boats of a practice.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BoatAssignment {
+    /// Fill each boat before moving on to the next.
+    #[default]
+    InOrder,
+    /// Seat members in the boat for their experience level first.
+    BySkill,
+}
+
+/// One boat on the water with its own roster and role seats. Side-less
+/// sessions use a single boat with everyone on the left list.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Boat {
+    pub name: String,
+    /// Experience level the boat is meant for when assigning by skill.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skill_group: Option<Experience>,
+    pub left_side: Vec<Option<ObjectId>>,
+    pub right_side: Vec<Option<ObjectId>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub steerer: Option<ObjectId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drummer: Option<ObjectId>,
+}
+
+impl Boat {
+    pub fn new(name: String, seats_per_side: usize, has_sides: bool) -> Self {
+        Self {
+            name,
+            skill_group: None,
+            left_side: vec![None; seats_per_side],
+            right_side: vec![None; if has_sides { seats_per_side } else { 0 }],
+            steerer: None,
+            drummer: None,
+        }
+    }
+
+    pub fn spots(&self, side: &Side) -> &Vec<Option<ObjectId>> {
+        match side {
+            Side::Right => &self.right_side,
+            _ => &self.left_side,
+        }
+    }
+
+    fn spots_mut(&mut self, side: &Side) -> &mut Vec<Option<ObjectId>> {
+        match side {
+            Side::Right => &mut self.right_side,
+            _ => &mut self.left_side,
+        }
+    }
+
+    fn role_seat_mut(&mut self, role: &BoatRole) -> &mut Option<ObjectId> {
+        match role {
+            BoatRole::Steerer => &mut self.steerer,
+            BoatRole::Drummer => &mut self.drummer,
+        }
+    }
+
+    fn count_side(&self, side: &Side) -> usize {
+        self.spots(side).iter().filter(|spot| spot.is_some()).count()
+    }
+
+    fn free_seat(&self, side: &Side) -> Option<usize> {
+        self.spots(side).iter().position(|spot| spot.is_none())
+    }
+
+    /// Everyone with a seat in the boat: role seats, then both sides.
+    pub fn seated(&self) -> impl Iterator<Item = ObjectId> + '_ {
+        self.steerer
+            .iter()
+            .chain(self.drummer.iter())
+            .chain(self.left_side.iter().flatten())
+            .chain(self.right_side.iter().flatten())
+            .copied()
+    }
+}
+
+/// Where a member sits in a practice.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SeatRef {
+    Role { boat: usize, role: BoatRole },
+    Spot { boat: usize, side: Side, pos: usize },
+}
+
+/// Where a member is on a practice's roster.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "list")]
+pub enum Placement {
+    Seat { boat: usize, side: Side, seat: usize },
+    Role { boat: usize, role: BoatRole },
+    Waitlist { side: Side, position: usize },
+    /// Entered in a lottery that hasn't been drawn yet.
+    Lottery,
+}
+
+impl Placement {
+    pub fn side(&self) -> Option<Side> {
+        match self {
+            Placement::Seat { side, .. } | Placement::Waitlist { side, .. } => Some(side.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn index(&self) -> Option<usize> {
+        match self {
+            Placement::Seat { seat, .. } => Some(*seat),
+            Placement::Waitlist { position, .. } => Some(*position),
+            _ => None,
+        }
+    }
+
+    pub fn is_seated(&self) -> bool {
+        matches!(self, Placement::Seat { .. } | Placement::Role { .. })
+    }
+}
+
+/// Set by an exec to lock or reopen signups regardless of the signup window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ManualLock {
+    Locked,
+    Unlocked,
+}
+
+/// A guest on a practice and the member who brought them.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PracticeGuest {
+    pub guest_id: ObjectId,
+    pub host_id: ObjectId,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Cancellation {
+    pub reason: String,
+    pub cancelled_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Practice {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
+    /// Bumped on every write so conditional updates can detect a concurrent one.
+    #[serde(default)]
+    pub revision: i64,
+    #[serde(default)]
+    pub practice_type: PracticeType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub series_id: Option<ObjectId>,
     pub date: DateTime<Utc>,
     pub start_time: DateTime<Utc>,
     pub end_time: DateTime<Utc>,
-    pub left_side: Vec<Option<ObjectId>>,
-    pub right_side: Vec<Option<ObjectId>>,
+    pub signup_opens_at: DateTime<Utc>,
+    pub signup_closes_at: DateTime<Utc>,
+    pub waitlist_transfer_at: DateTime<Utc>,
+    /// Tiers of members whose signups open before `signup_opens_at`.
+    #[serde(default)]
+    pub early_access: Vec<EarlyAccess>,
+    pub boats: Vec<Boat>,
+    #[serde(default)]
+    pub boat_assignment: BoatAssignment,
     pub left_side_waitlist: Vec<Option<ObjectId>>,
     pub right_side_waitlist: Vec<Option<ObjectId>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cancellation: Option<Cancellation>,
+    /// The practice whose priority seats this practice's waitlist gets.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub waitlist_feeds_into: Option<ObjectId>,
+    /// Ambidextrous paddlers on the roster who can be moved between sides.
+    #[serde(default)]
+    pub flex_paddlers: Vec<ObjectId>,
+    /// Seats pinned by an exec, applied before the lineup builder runs.
+    #[serde(default)]
+    pub lineup_overrides: Vec<LineupOverride>,
+    /// Set when seats are given out by a draw instead of first come, first served.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lottery: Option<Lottery>,
+    /// Guests take seats like members; this records who brought them.
+    #[serde(default)]
+    pub guests: Vec<PracticeGuest>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manual_lock: Option<ManualLock>,
 }
 
 impl Practice {
-    pub fn new(date: DateTime<Utc>, start_time: DateTime<Utc>) -> Self {
-        let end_time = start_time + chrono::Duration::hours(1);
+    pub fn new(
+        practice_type: PracticeType,
+        date: DateTime<Utc>,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        window: &SignupWindow,
+    ) -> Result<Self, PracticeError> {
+        if end_time <= start_time {
+            return Err(PracticeError::InvalidTimes);
+        }
 
-        Self {
+        let (seats, waitlist) = practice_type.default_capacity();
+
+        // Side-less sessions keep a single roster on the left lists
+        let right_waitlist = if practice_type.has_sides() { waitlist } else { 0 };
+
+        let practice = Self {
             id: None,
+            revision: 0,
+            practice_type,
+            series_id: None,
             date,
             start_time,
             end_time,
-            left_side: vec![None; 17],
-            right_side: vec![None; 17],
-            left_side_waitlist: vec![None; 6],
-            right_side_waitlist: vec![None; 6],
-        }
+            signup_opens_at: start_time - window.opens_before(),
+            signup_closes_at: start_time - window.closes_before(),
+            waitlist_transfer_at: start_time - window.waitlist_transfer_before(),
+            early_access: Vec::new(),
+            boats: vec![Boat::new(
+                "Boat 1".to_string(),
+                seats,
+                practice_type.has_sides(),
+            )],
+            boat_assignment: BoatAssignment::InOrder,
+            left_side_waitlist: vec![None; waitlist],
+            right_side_waitlist: vec![None; right_waitlist],
+            cancellation: None,
+            waitlist_feeds_into: None,
+            flex_paddlers: Vec::new(),
+            lineup_overrides: Vec::new(),
+            lottery: None,
+            guests: Vec::new(),
+            manual_lock: None,
+        };
+        practice.validate_signup_window()?;
+
+        Ok(practice)
     }
 
-    pub fn from_sheet_data(data: &PracticeSheetData) -> Self {
+    pub fn from_sheet_data(data: &PracticeSheetData, window: &SignupWindow) -> Self {
+        let practice_type = PracticeType::Water;
+
         Self {
             id: None,
+            revision: 0,
+            practice_type,
+            series_id: None,
             date: data.date,
             start_time: data.date,
-            end_time: data.date + chrono::Duration::hours(1),
-            left_side: vec![None; 17], // We'll update these after creating users
-            right_side: vec![None; 17],
+            end_time: data
+                .end_time
+                .unwrap_or(data.date + practice_type.default_duration()),
+            signup_opens_at: data.date - window.opens_before(),
+            signup_closes_at: data.date - window.closes_before(),
+            waitlist_transfer_at: data.date - window.waitlist_transfer_before(),
+            early_access: Vec::new(),
+            // We'll update these after creating users
+            boats: data
+                .boats
+                .iter()
+                .enumerate()
+                .map(|(i, boat)| {
+                    Boat::new(
+                        boat.name.clone().unwrap_or_else(|| format!("Boat {}", i + 1)),
+                        boat.left_side.len().max(boat.right_side.len()),
+                        true,
+                    )
+                })
+                .collect(),
+            boat_assignment: BoatAssignment::InOrder,
             left_side_waitlist: vec![None; 6],
             right_side_waitlist: vec![None; 6],
+            cancellation: None,
+            waitlist_feeds_into: None,
+            flex_paddlers: Vec::new(),
+            lineup_overrides: Vec::new(),
+            lottery: None,
+            guests: Vec::new(),
+            manual_lock: None,
+        }
+    }
+
+    pub fn validate_signup_window(&self) -> Result<(), PracticeError> {
+        if self.signup_closes_at <= self.signup_opens_at
+            || self.waitlist_transfer_at > self.earliest_signup_open()
+            || self.lottery.as_ref().is_some_and(|lottery| {
+                lottery.draws_at <= self.earliest_signup_open()
+                    || lottery.draws_at >= self.signup_closes_at
+            })
+        {
+            return Err(PracticeError::InvalidSignupWindow);
         }
+        Ok(())
+    }
+
+    /// Gives tiers early access, moving the waitlist transfer back if needed
+    /// so carried-over members still get their seats before anyone signs up.
+    pub fn set_early_access(&mut self, early_access: Vec<EarlyAccess>) {
+        self.early_access = early_access;
+        self.waitlist_transfer_at = self
+            .waitlist_transfer_at
+            .min(self.earliest_signup_open() - Duration::minutes(1));
+    }
+
+    /// When signups open for a member in the given tiers.
+    pub fn signup_opens_for(&self, tiers: &[SignupTier]) -> DateTime<Utc> {
+        self.early_access
+            .iter()
+            .filter(|early| tiers.contains(&early.tier))
+            .map(|early| early.opens_at(self.signup_opens_at))
+            .fold(self.signup_opens_at, DateTime::min)
     }
 
-    //TO DEPRCEATE
-    pub fn is_locked(&self) -> bool {
+    /// When the first tier can sign up.
+    pub fn earliest_signup_open(&self) -> DateTime<Utc> {
+        self.early_access
+            .iter()
+            .map(|early| early.opens_at(self.signup_opens_at))
+            .fold(self.signup_opens_at, DateTime::min)
+    }
+
+    /// Signups are locked for a member outside of when they open for their
+    /// tiers and `signup_closes_at`, unless an exec locked or reopened them.
+    pub fn is_locked_for(&self, tiers: &[SignupTier]) -> bool {
         let now = Utc::now();
-        let unlock_time = self.start_time - chrono::Duration::hours(1);
 
-        now < unlock_time
+        match self.manual_lock {
+            Some(ManualLock::Locked) => true,
+            Some(ManualLock::Unlocked) => false,
+            None => now < self.signup_opens_for(tiers) || now >= self.signup_closes_at,
+        }
     }
 
-    fn count_side(&self, side: &Side) -> usize {
-        let spots = match side {
-            Side::Left => &self.left_side,
-            Side::Right => &self.right_side,
-            _ => panic!(),
+    /// Moves the practice, shifting its signup window along with it.
+    pub fn reschedule(&mut self, start_time: DateTime<Utc>, end_time: DateTime<Utc>) {
+        let shift = start_time - self.start_time;
+
+        self.date = start_time;
+        self.start_time = start_time;
+        self.end_time = end_time;
+        self.signup_opens_at += shift;
+        self.signup_closes_at += shift;
+        self.waitlist_transfer_at += shift;
+        if let Some(lottery) = &mut self.lottery {
+            lottery.draws_at += shift;
+        }
+    }
+
+    /// Whether signups currently go into the lottery instead of taking seats.
+    pub fn lottery_is_open(&self) -> bool {
+        self.lottery.as_ref().is_some_and(Lottery::is_open)
+    }
+
+    /// Puts a member in the lottery. Signup checks other than the seat
+    /// search are the same as for `add_participant`.
+    pub fn enter_lottery(&mut self, user: &User, waitlist_only: bool) -> Result<(), PracticeError> {
+        if self.is_cancelled() {
+            return Err(PracticeError::Cancelled);
+        }
+        if self.is_locked_for(&user.signup_tiers()) {
+            return Err(PracticeError::Locked);
+        }
+
+        let user_id = user.id.ok_or(PracticeError::NoUserId)?;
+        if self.participants().any(|id| id == user_id) {
+            return Err(PracticeError::AlreadySignedUp);
+        }
+
+        let lottery = self.lottery.as_mut().ok_or(PracticeError::Locked)?;
+        lottery.entries.push(LotteryEntry {
+            user_id,
+            side: user.side.clone(),
+            experience: user.experience,
+            waitlist_only,
+            entered_at: Utc::now(),
+        });
+
+        Ok(())
+    }
+
+    /// Draws the lottery and seats entrants in draw order: a seat on their
+    /// side (either side for ambidextrous members), else the waitlist.
+    pub fn run_lottery(&mut self, weights: Vec<f64>, seed: i64) -> Option<&LotteryDraw> {
+        let lottery = self.lottery.as_ref().filter(|lottery| lottery.is_open())?;
+        let order = lottery.draw_order(&weights, seed);
+        let entries = lottery.entries.clone();
+
+        let mut draw = LotteryDraw {
+            seed,
+            drawn_at: Utc::now(),
+            weights,
+            order: order.clone(),
+            seated: Vec::new(),
+            waitlisted: Vec::new(),
+            dropped: Vec::new(),
         };
-        spots.iter().filter(|spot| spot.is_some()).count()
+
+        for user_id in order {
+            let Some(entry) = entries.iter().find(|entry| entry.user_id == user_id) else {
+                continue;
+            };
+
+            let side = self.determine_side(&entry.side);
+            let flexible = entry.side == Side::NA && self.practice_type.has_sides();
+
+            let mut sides = vec![side.clone()];
+            if flexible {
+                sides.push(if side == Side::Left { Side::Right } else { Side::Left });
+            }
+
+            let order = self.boat_order(entry.experience);
+            let seat = sides
+                .iter()
+                .filter(|_| !entry.waitlist_only)
+                .find_map(|side| {
+                    self.free_seat(side, &order)
+                        .map(|(boat, pos)| (side.clone(), boat, pos))
+                });
+
+            if let Some((seat_side, boat, pos)) = seat {
+                self.boats[boat].spots_mut(&seat_side)[pos] = Some(user_id);
+                draw.seated.push(user_id);
+            } else if let Some(spot) = self
+                .waitlist_mut(&side)
+                .iter_mut()
+                .find(|spot| spot.is_none())
+            {
+                *spot = Some(user_id);
+                draw.waitlisted.push(user_id);
+            } else {
+                draw.dropped.push(user_id);
+                continue;
+            }
+
+            if flexible {
+                self.flex_paddlers.push(user_id);
+            }
+        }
+
+        let lottery = self.lottery.as_mut()?;
+        lottery.draw = Some(draw);
+        lottery.draw.as_ref()
+    }
+
+    fn count_side(&self, side: &Side) -> usize {
+        self.boats.iter().map(|boat| boat.count_side(side)).sum()
+    }
+
+    /// Boat indices in the order a member with the given experience is seated in.
+    fn boat_order(&self, experience: Option<Experience>) -> Vec<usize> {
+        let mut order = (0..self.boats.len()).collect::<Vec<_>>();
+
+        if self.boat_assignment == BoatAssignment::BySkill {
+            // Their own group first, then open boats, then any other group
+            order.sort_by_key(|&i| match self.boats[i].skill_group {
+                Some(group) if Some(group) == experience => 0,
+                None => 1,
+                Some(_) => 2,
+            });
+        }
+
+        order
+    }
+
+    /// The first free seat on a side as `(boat, position)`, following `order`.
+    fn free_seat(&self, side: &Side, order: &[usize]) -> Option<(usize, usize)> {
+        order
+            .iter()
+            .find_map(|&boat| self.boats[boat].free_seat(side).map(|pos| (boat, pos)))
+    }
+
+    /// Adds a boat and seats as many waitlisted members in it as fit.
+    /// Returns the members that got a seat.
+    pub fn add_boat(&mut self, boat: Boat) -> Vec<ObjectId> {
+        self.boats.push(boat);
+        let index = self.boats.len() - 1;
+        let mut promoted = Vec::new();
+
+        for side in [Side::Left, Side::Right] {
+            while let Some(pos) = self.boats[index].free_seat(&side) {
+                let waitlist = self.waitlist_mut(&side);
+                let Some(head) = waitlist.iter().position(|spot| spot.is_some()) else {
+                    break;
+                };
+
+                let user_id = waitlist[head].take();
+                self.boats[index].spots_mut(&side)[pos] = user_id;
+                promoted.extend(user_id);
+            }
+        }
+
+        promoted
     }
 
     pub(crate) fn determine_side(&self, side: &Side) -> Side {
+        if !self.practice_type.has_sides() {
+            return Side::Left;
+        }
+
         match side {
             Side::NA => {
                 if self.count_side(&Side::Right) >= self.count_side(&Side::Left) {
-                    return Side::Left;
+                    Side::Left
                 } else {
-                    return Side::Right;
+                    Side::Right
                 }
             }
-            _ => return side.clone(),
+            _ => side.clone(),
         }
     }
 
-    pub(crate) async fn add_participant(
+    /// Signs a member up, returning whether they got a seat. Members that
+    /// are `waitlist_only` go straight to the waitlist.
+    pub(crate) fn add_participant(
         &mut self,
-        discord_id: &str,
+        user: &User,
         side: &Side,
-        db: Arc<DB>,
+        role: Option<&BoatRole>,
+        waitlist_only: bool,
     ) -> Result<bool, PracticeError> {
-        if self.is_locked() {
-            return Err(PracticeError::Locked);
+        if self.is_cancelled() {
+            return Err(PracticeError::Cancelled);
         }
 
-        let user = db
-            .get_user_by_discord_id(discord_id)
-            .await
-            .map_err(|e| PracticeError::DatabaseError(e.to_string()))?
-            .ok_or(PracticeError::UserNotFound)?;
+        if self.is_locked_for(&user.signup_tiers()) {
+            return Err(PracticeError::Locked);
+        }
 
         let user_id = user.id.ok_or(PracticeError::NoUserId)?;
 
-        let (spots, waitlist) = match side {
-            Side::Left => (&mut self.left_side, &mut self.left_side_waitlist),
-            Side::Right => (&mut self.right_side, &mut self.right_side_waitlist),
-            _ => panic!(),
-        };
+        if self.participants().any(|id| id == user_id) {
+            return Err(PracticeError::AlreadySignedUp);
+        }
 
-        if let Some(spot) = spots.iter_mut().find(|spot| spot.is_none()) {
-            *spot = Some(user_id);
+        if let Some(role) = role {
+            if !self.practice_type.has_boat_roles() {
+                return Err(PracticeError::NoRoleSeat(role.clone()));
+            }
+            if !user.qualifications.contains(role) {
+                return Err(PracticeError::NotQualified(role.clone()));
+            }
+
+            for boat in self.boat_order(user.experience) {
+                let seat = self.boats[boat].role_seat_mut(role);
+                if seat.is_none() {
+                    *seat = Some(user_id);
+                    return Ok(true);
+                }
+            }
+            return Err(PracticeError::RoleTaken(role.clone()));
+        }
+
+        self.seat_paddler(user_id, &user.side, side, user.experience, waitlist_only)
+    }
+
+    /// Signs a guest up as a member's plus-one. Guests count against the
+    /// practice's capacity and are seated the same way members are.
+    pub fn add_guest(
+        &mut self,
+        guest: &Guest,
+        host: &User,
+        policy: &GuestPolicy,
+    ) -> Result<bool, PracticeError> {
+        if self.is_cancelled() {
+            return Err(PracticeError::Cancelled);
+        }
+        if self.is_locked_for(&host.signup_tiers()) {
+            return Err(PracticeError::Locked);
+        }
+        if self.lottery_is_open() {
+            return Err(PracticeError::LotteryOpen);
+        }
+
+        let guest_id = guest.id.ok_or(PracticeError::NoUserId)?;
+        let host_id = host.id.ok_or(PracticeError::NoUserId)?;
+
+        if self.participants().any(|id| id == guest_id) {
+            return Err(PracticeError::AlreadySignedUp);
+        }
+        if self.guests.len() >= policy.per_practice {
+            return Err(PracticeError::GuestLimit);
+        }
+        if !host.user_type.is_exec()
+            && self.guests.iter().filter(|entry| entry.host_id == host_id).count() >= policy.per_member
+        {
+            return Err(PracticeError::HostGuestLimit);
+        }
+
+        let seated = self.seat_paddler(guest_id, &guest.side, &guest.side, None, false)?;
+        self.guests.push(PracticeGuest { guest_id, host_id });
+
+        Ok(seated)
+    }
+
+    /// Seats a paddler on `side`, or waitlists them when it's full.
+    /// `paddles` is the side they can paddle, `NA` making them flex.
+    fn seat_paddler(
+        &mut self,
+        user_id: ObjectId,
+        paddles: &Side,
+        side: &Side,
+        experience: Option<Experience>,
+        waitlist_only: bool,
+    ) -> Result<bool, PracticeError> {
+        // Ambidextrous paddlers get a side for now but can be moved at lock time
+        if *paddles == Side::NA && self.practice_type.has_sides() {
+            self.flex_paddlers.push(user_id);
+        }
+
+        let side = self.determine_side(side);
+
+        if let Some((boat, pos)) = self
+            .free_seat(&side, &self.boat_order(experience))
+            .filter(|_| !waitlist_only)
+        {
+            self.boats[boat].spots_mut(&side)[pos] = Some(user_id);
             return Ok(true);
         }
 
-        if let Some(spot) = waitlist.iter_mut().find(|spot| spot.is_none()) {
+        if let Some(spot) = self.waitlist_mut(&side).iter_mut().find(|spot| spot.is_none()) {
             *spot = Some(user_id);
             return Ok(false);
         }
 
+        self.flex_paddlers.retain(|id| *id != user_id);
         Err(PracticeError::Full)
     }
 
-    pub fn transfer_waitlist(&mut self, prev: &Practice) {
-        for waitlist_left in prev.left_side_waitlist.iter().flatten() {
-            if let Some(empty_spot) = self.left_side.iter_mut().find(|spot| spot.is_none()) {
-                *empty_spot = Some(*waitlist_left);
-            } else {
-                panic!();
+    /// Moves flex paddlers across at lock time so that no side keeps people on
+    /// its waitlist while the other has free seats, then evens out the sides.
+    pub fn rebalance_flex(&mut self) -> RebalanceReport {
+        let mut report = RebalanceReport::default();
+
+        if !self.practice_type.has_sides() {
+            return report;
+        }
+
+        while self.rebalance_step(&Side::Left, &Side::Right, &mut report)
+            || self.rebalance_step(&Side::Right, &Side::Left, &mut report)
+        {}
+
+        report
+    }
+
+    fn rebalance_step(&mut self, from: &Side, to: &Side, report: &mut RebalanceReport) -> bool {
+        let order = self.boat_order(None);
+        let Some((boat, free_seat)) = self.free_seat(to, &order) else {
+            return false;
+        };
+
+        // A flex paddler waiting on one side takes a free seat on the other
+        if let Some(pos) = self.flex_position(self.waitlist(from)) {
+            let user_id = self.waitlist_mut(from)[pos].take().unwrap();
+            self.boats[boat].spots_mut(to)[free_seat] = Some(user_id);
+            report.moved.push(user_id);
+            return true;
+        }
+
+        let waitlist_head = self.waitlist(from).iter().position(|spot| spot.is_some());
+        let to_waitlist_empty = self.waitlist(to).iter().all(|spot| spot.is_none());
+
+        // Seated flex paddlers only cross over within their own boat
+        for boat in order {
+            let Some(free_seat) = self.boats[boat].free_seat(to) else {
+                continue;
+            };
+            let Some(pos) = self.flex_position(self.boats[boat].spots(from)) else {
+                continue;
+            };
+            let unbalanced =
+                self.boats[boat].count_side(from) > self.boats[boat].count_side(to) + 1;
+
+            if waitlist_head.is_none() && !(unbalanced && to_waitlist_empty) {
+                continue;
+            }
+
+            // The flex paddler crosses over, freeing their seat for the head
+            // of their old side's waitlist
+            let user_id = self.boats[boat].spots_mut(from)[pos].take().unwrap();
+            self.boats[boat].spots_mut(to)[free_seat] = Some(user_id);
+            report.moved.push(user_id);
+
+            if let Some(head) = waitlist_head {
+                let promoted = self.waitlist_mut(from)[head].take();
+                self.boats[boat].spots_mut(from)[pos] = promoted;
+                report.promoted.extend(promoted);
             }
+
+            return true;
         }
 
-        for waitlist_right in prev.right_side_waitlist.iter().flatten() {
-            if let Some(empty_spot) = self.right_side.iter_mut().find(|spot| spot.is_none()) {
-                *empty_spot = Some(*waitlist_right);
+        false
+    }
+
+    /// Position of the most recently listed flex paddler on a list.
+    fn flex_position(&self, list: &[Option<ObjectId>]) -> Option<usize> {
+        list.iter()
+            .rposition(|spot| spot.is_some_and(|id| self.flex_paddlers.contains(&id)))
+    }
+
+    /// The boat and side a user has a seat on, if any.
+    pub fn seat_of(&self, user_id: ObjectId) -> Option<(usize, Side)> {
+        self.boats.iter().enumerate().find_map(|(i, boat)| {
+            if boat.left_side.contains(&Some(user_id)) {
+                Some((i, Side::Left))
+            } else if boat.right_side.contains(&Some(user_id)) {
+                Some((i, Side::Right))
             } else {
-                panic!();
+                None
+            }
+        })
+    }
+
+    /// The seat a user holds, role seats included.
+    pub fn seat_ref(&self, user_id: ObjectId) -> Option<SeatRef> {
+        for (boat, seats) in self.boats.iter().enumerate() {
+            for role in [BoatRole::Steerer, BoatRole::Drummer] {
+                let holder = match role {
+                    BoatRole::Steerer => seats.steerer,
+                    BoatRole::Drummer => seats.drummer,
+                };
+                if holder == Some(user_id) {
+                    return Some(SeatRef::Role { boat, role });
+                }
+            }
+        }
+
+        let (boat, side) = self.seat_of(user_id)?;
+        let pos = self.boats[boat]
+            .spots(&side)
+            .iter()
+            .position(|spot| *spot == Some(user_id))?;
+
+        Some(SeatRef::Spot { boat, side, pos })
+    }
+
+    /// Whether a member could sit in a seat: role seats need the
+    /// qualification, and side seats a member who paddles that side.
+    pub fn can_take_seat(&self, seat: &SeatRef, user: &User) -> Result<(), PracticeError> {
+        match seat {
+            SeatRef::Role { role, .. } => {
+                if !user.qualifications.contains(role) {
+                    return Err(PracticeError::NotQualified(role.clone()));
+                }
             }
+            SeatRef::Spot { side, .. } => self.check_side(user, side)?,
         }
+        Ok(())
+    }
+
+    fn check_side(&self, user: &User, side: &Side) -> Result<(), PracticeError> {
+        if self.practice_type.has_sides() && user.side != Side::NA && user.side != *side {
+            return Err(PracticeError::WrongSide(side.clone()));
+        }
+        Ok(())
+    }
+
+    /// Gives a seat to another member, taking them off the waitlist if they
+    /// were on it. Returns the member who held the seat.
+    pub fn hand_over_seat(&mut self, seat: &SeatRef, to: &User) -> Result<ObjectId, PracticeError> {
+        let to_id = to.id.ok_or(PracticeError::NoUserId)?;
+        self.can_take_seat(seat, to)?;
+
+        let spot = match seat {
+            SeatRef::Role { boat, role } => self.boats[*boat].role_seat_mut(role),
+            SeatRef::Spot { boat, side, pos } => &mut self.boats[*boat].spots_mut(side)[*pos],
+        };
+        let from_id = spot.ok_or(PracticeError::NotSeated)?;
+        *spot = Some(to_id);
+
+        self.flex_paddlers.retain(|id| *id != from_id && *id != to_id);
+        self.lineup_overrides.retain(|pinned| pinned.user_id != from_id);
+        for waitlist in [&mut self.left_side_waitlist, &mut self.right_side_waitlist] {
+            for spot in waitlist.iter_mut().filter(|spot| **spot == Some(to_id)) {
+                *spot = None;
+            }
+        }
+
+        if matches!(seat, SeatRef::Spot { .. }) && to.side == Side::NA && self.practice_type.has_sides() {
+            self.flex_paddlers.push(to_id);
+        }
+
+        Ok(from_id)
+    }
+
+    /// The side a user has a seat on, if any.
+    pub fn seated_side(&self, user_id: ObjectId) -> Option<Side> {
+        self.seat_of(user_id).map(|(_, side)| side)
+    }
+
+    /// Gives the waitlist of a previous practice priority in this one. Members
+    /// get a free seat on their side if there is one, otherwise they go ahead
+    /// of everyone already on the waitlist. Flexible members (`flexible`) may
+    /// take a seat on either side.
+    pub fn transfer_waitlist(
+        &mut self,
+        prev: &Practice,
+        flexible: &[ObjectId],
+    ) -> WaitlistTransferReport {
+        let mut report = WaitlistTransferReport::default();
+        let mut waitlist_heads = [0, 0];
+
+        let candidates = prev
+            .left_side_waitlist
+            .iter()
+            .flatten()
+            .map(|id| (*id, Side::Left))
+            .chain(
+                prev.right_side_waitlist
+                    .iter()
+                    .flatten()
+                    .map(|id| (*id, Side::Right)),
+            )
+            .collect::<Vec<_>>();
+
+        for (user_id, side) in candidates {
+            if self.participants().any(|id| id == user_id) {
+                report.duplicates.push(user_id);
+                continue;
+            }
+
+            let side = self.determine_side(&side);
+            let other_side = match side {
+                Side::Left => Side::Right,
+                _ => Side::Left,
+            };
+
+            let mut sides = vec![side.clone()];
+            if flexible.contains(&user_id) && self.practice_type.has_sides() {
+                sides.push(other_side);
+            }
+
+            let order = self.boat_order(None);
+            let seat = sides.iter().find_map(|side| {
+                self.free_seat(side, &order)
+                    .map(|(boat, pos)| (side.clone(), boat, pos))
+            });
+
+            if flexible.contains(&user_id) && self.practice_type.has_sides() {
+                self.flex_paddlers.push(user_id);
+            }
+
+            if let Some((seat_side, boat, pos)) = seat {
+                self.boats[boat].spots_mut(&seat_side)[pos] = Some(user_id);
+                report.placed.push(user_id);
+                continue;
+            }
+
+            let head = &mut waitlist_heads[if side == Side::Left { 0 } else { 1 }];
+            let waitlist = self.waitlist_mut(&side);
+
+            match waitlist.iter().rposition(|spot| spot.is_none()) {
+                Some(free) => {
+                    waitlist.remove(free);
+                    waitlist.insert(*head, Some(user_id));
+                    *head += 1;
+                    report.waitlisted.push(user_id);
+                }
+                None => {
+                    self.flex_paddlers.retain(|id| *id != user_id);
+                    report.dropped.push(user_id);
+                }
+            }
+        }
+
+        report
+    }
+
+    pub fn waitlist(&self, side: &Side) -> &Vec<Option<ObjectId>> {
+        match side {
+            Side::Right => &self.right_side_waitlist,
+            _ => &self.left_side_waitlist,
+        }
+    }
+
+    fn waitlist_mut(&mut self, side: &Side) -> &mut Vec<Option<ObjectId>> {
+        match side {
+            Side::Right => &mut self.right_side_waitlist,
+            _ => &mut self.left_side_waitlist,
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_some()
+    }
+
+    /// Everyone on the practice: each boat in turn, the waitlists, then
+    /// lottery entrants waiting for the draw.
+    pub fn participants(&self) -> impl Iterator<Item = ObjectId> + '_ {
+        self.boats
+            .iter()
+            .flat_map(Boat::seated)
+            .chain(self.left_side_waitlist.iter().flatten().copied())
+            .chain(self.right_side_waitlist.iter().flatten().copied())
+            .chain(
+                self.lottery
+                    .iter()
+                    .filter(|lottery| lottery.is_open())
+                    .flat_map(|lottery| lottery.entries.iter().map(|entry| entry.user_id)),
+            )
+    }
+
+    /// Everyone on the practice and where they are.
+    pub fn placements(&self) -> HashMap<ObjectId, Placement> {
+        let mut placements = HashMap::new();
+
+        for (boat, seats) in self.boats.iter().enumerate() {
+            for side in [Side::Left, Side::Right] {
+                for (seat, spot) in seats.spots(&side).iter().enumerate() {
+                    if let Some(user_id) = spot {
+                        let side = side.clone();
+                        placements.insert(*user_id, Placement::Seat { boat, side, seat });
+                    }
+                }
+            }
+            for (role, holder) in [(BoatRole::Steerer, seats.steerer), (BoatRole::Drummer, seats.drummer)] {
+                if let Some(user_id) = holder {
+                    placements.insert(user_id, Placement::Role { boat, role });
+                }
+            }
+        }
+
+        for (side, waitlist) in [
+            (Side::Left, &self.left_side_waitlist),
+            (Side::Right, &self.right_side_waitlist),
+        ] {
+            for (position, spot) in waitlist.iter().enumerate() {
+                if let Some(user_id) = spot {
+                    let side = side.clone();
+                    placements.insert(*user_id, Placement::Waitlist { side, position });
+                }
+            }
+        }
+
+        if let Some(lottery) = self.lottery.as_ref().filter(|lottery| lottery.is_open()) {
+            for entry in &lottery.entries {
+                placements.insert(entry.user_id, Placement::Lottery);
+            }
+        }
+
+        placements
     }
 
     pub fn is_future(&self) -> bool {
         self.start_time > Utc::now()
     }
 
-    pub async fn remove_participant(
-        &mut self,
-        discord_id: &str,
-        db: Arc<DB>,
-    ) -> Result<Option<ObjectId>, PracticeError> {
-        let user = db
-            .get_user_by_discord_id(discord_id)
-            .await
-            .map_err(|e| PracticeError::DatabaseError(e.to_string()))?
-            .ok_or(PracticeError::UserNotFound)?;
+    /// The calendar day of the practice in the club's timezone.
+    pub fn local_date(&self) -> NaiveDate {
+        self.start_time.with_timezone(&New_York).date_naive()
+    }
+
+    pub fn remove_participant(&mut self, user: &User) -> Result<Option<ObjectId>, PracticeError> {
+        self.remove_paddler(user.id.ok_or(PracticeError::NoUserId)?)
+    }
+
+    /// Takes anyone off the practice by id, guests included, promoting from
+    /// the waitlist like [`Practice::remove_participant`].
+    pub fn remove_by_id(&mut self, user_id: ObjectId) -> Result<Option<ObjectId>, PracticeError> {
+        let promoted = self.remove_paddler(user_id)?;
+        self.guests.retain(|entry| entry.guest_id != user_id);
+        Ok(promoted)
+    }
+
+    /// Puts a member exactly where an exec says, moving them if they're
+    /// already on the roster. The signup window doesn't apply, but the spot
+    /// has to exist and be free and the member has to be able to take it.
+    /// Nobody is promoted into a seat the member leaves.
+    pub fn place_member(&mut self, user: &User, target: &Placement) -> Result<(), PracticeError> {
+        if self.is_cancelled() {
+            return Err(PracticeError::Cancelled);
+        }
 
         let user_id = user.id.ok_or(PracticeError::NoUserId)?;
+        let taken = |holder: Option<ObjectId>| holder.is_some_and(|id| id != user_id);
 
-        // Check left side main list
-        if let Some(pos) = self
-            .left_side
-            .iter()
-            .position(|id| id.as_ref() == Some(&user_id))
-        {
-            self.left_side[pos] = None;
+        match target {
+            Placement::Seat { boat, side, seat } => {
+                if self.practice_type.has_sides() && *side == Side::NA {
+                    return Err(PracticeError::NoSuchSpot);
+                }
+                let holder = self
+                    .boats
+                    .get(*boat)
+                    .and_then(|seats| seats.spots(side).get(*seat))
+                    .ok_or(PracticeError::NoSuchSpot)?;
+                if taken(*holder) {
+                    return Err(PracticeError::SpotTaken);
+                }
+                self.check_side(user, side)?;
+            }
+            Placement::Role { boat, role } => {
+                if !self.practice_type.has_boat_roles() {
+                    return Err(PracticeError::NoRoleSeat(role.clone()));
+                }
+                let seats = self.boats.get(*boat).ok_or(PracticeError::NoSuchSpot)?;
+                let holder = match role {
+                    BoatRole::Steerer => seats.steerer,
+                    BoatRole::Drummer => seats.drummer,
+                };
+                if taken(holder) {
+                    return Err(PracticeError::RoleTaken(role.clone()));
+                }
+                self.can_take_seat(&SeatRef::Role { boat: *boat, role: role.clone() }, user)?;
+            }
+            Placement::Waitlist { side, .. } => {
+                if self.practice_type.has_sides() && *side == Side::NA {
+                    return Err(PracticeError::NoSuchSpot);
+                }
+                let waitlist = self.waitlist(side);
+                if waitlist.iter().filter(|spot| taken(**spot)).count() >= waitlist.len() {
+                    return Err(PracticeError::Full);
+                }
+                self.check_side(user, side)?;
+            }
+            Placement::Lottery => return Err(PracticeError::NoSuchSpot),
+        }
 
-            // Check left waitlist for replacement
-            if let Some(waitlist_pos) = self.left_side_waitlist.iter().position(|id| id.is_some()) {
-                let waitlist_user = self.left_side_waitlist[waitlist_pos].take();
-                self.left_side[pos] = waitlist_user;
-                return Ok(waitlist_user);
+        self.vacate(user_id);
+
+        match target {
+            Placement::Seat { boat, side, seat } => {
+                self.boats[*boat].spots_mut(side)[*seat] = Some(user_id);
+            }
+            Placement::Role { boat, role } => {
+                *self.boats[*boat].role_seat_mut(role) = Some(user_id);
             }
-            return Ok(None);
+            Placement::Waitlist { side, position } => {
+                let mut queue = self.waitlist(side).iter().flatten().copied().collect::<Vec<_>>();
+                queue.insert((*position).min(queue.len()), user_id);
+                self.set_waitlist(side, queue);
+            }
+            Placement::Lottery => {}
         }
 
-        // Check right side main list
-        if let Some(pos) = self
-            .right_side
-            .iter()
-            .position(|id| id.as_ref() == Some(&user_id))
-        {
-            self.right_side[pos] = None;
+        if !matches!(target, Placement::Role { .. }) && user.side == Side::NA && self.practice_type.has_sides() {
+            self.flex_paddlers.push(user_id);
+        }
 
-            // Check right waitlist for replacement
-            if let Some(waitlist_pos) = self.right_side_waitlist.iter().position(|id| id.is_some())
-            {
-                let waitlist_user = self.right_side_waitlist[waitlist_pos].take();
-                self.right_side[pos] = waitlist_user;
-                return Ok(waitlist_user);
+        Ok(())
+    }
+
+    /// Puts a side's waitlist in the given order, which has to name everyone
+    /// already on it.
+    pub fn reorder_waitlist(&mut self, side: &Side, order: &[ObjectId]) -> Result<(), PracticeError> {
+        if self.is_cancelled() {
+            return Err(PracticeError::Cancelled);
+        }
+
+        let mut current = self.waitlist(side).iter().flatten().copied().collect::<Vec<_>>();
+        let mut requested = order.to_vec();
+        current.sort();
+        requested.sort();
+        if current != requested {
+            return Err(PracticeError::WaitlistMismatch);
+        }
+
+        self.set_waitlist(side, order.to_vec());
+        Ok(())
+    }
+
+    /// Clears every spot a member holds without promoting anyone.
+    fn vacate(&mut self, user_id: ObjectId) {
+        self.flex_paddlers.retain(|id| *id != user_id);
+        self.lineup_overrides.retain(|pinned| pinned.user_id != user_id);
+
+        if let Some(lottery) = self.lottery.as_mut().filter(|lottery| lottery.is_open()) {
+            lottery.entries.retain(|entry| entry.user_id != user_id);
+        }
+
+        for boat in self.boats.iter_mut() {
+            for role in [BoatRole::Steerer, BoatRole::Drummer] {
+                let seat = boat.role_seat_mut(&role);
+                if *seat == Some(user_id) {
+                    *seat = None;
+                }
+            }
+            for spot in boat.left_side.iter_mut().chain(boat.right_side.iter_mut()) {
+                if *spot == Some(user_id) {
+                    *spot = None;
+                }
             }
-            return Ok(None);
         }
 
-        // Check left waitlist
-        if let Some(pos) = self
-            .left_side_waitlist
-            .iter()
-            .position(|id| id.as_ref() == Some(&user_id))
-        {
-            self.left_side_waitlist[pos] = None;
-            return Ok(None);
+        for waitlist in [&mut self.left_side_waitlist, &mut self.right_side_waitlist] {
+            for spot in waitlist.iter_mut().filter(|spot| **spot == Some(user_id)) {
+                *spot = None;
+            }
         }
+    }
 
-        // Check right waitlist
-        if let Some(pos) = self
-            .right_side_waitlist
-            .iter()
-            .position(|id| id.as_ref() == Some(&user_id))
-        {
-            self.right_side_waitlist[pos] = None;
-            return Ok(None);
+    /// Rewrites a waitlist with `queue` at the front, keeping its size.
+    fn set_waitlist(&mut self, side: &Side, queue: Vec<ObjectId>) {
+        let waitlist = self.waitlist_mut(side);
+        let size = waitlist.len();
+
+        *waitlist = queue.into_iter().map(Some).collect();
+        waitlist.resize(size, None);
+    }
+
+    fn remove_paddler(&mut self, user_id: ObjectId) -> Result<Option<ObjectId>, PracticeError> {
+        self.flex_paddlers.retain(|id| *id != user_id);
+        self.lineup_overrides.retain(|pinned| pinned.user_id != user_id);
+
+        // Withdraw from a lottery that hasn't been drawn yet
+        if let Some(lottery) = self.lottery.as_mut().filter(|lottery| lottery.is_open()) {
+            if let Some(pos) = lottery.entries.iter().position(|entry| entry.user_id == user_id) {
+                lottery.entries.remove(pos);
+                return Ok(None);
+            }
+        }
+
+        // Check role seats
+        for boat in self.boats.iter_mut() {
+            for role in [BoatRole::Steerer, BoatRole::Drummer] {
+                let seat = boat.role_seat_mut(&role);
+                if *seat == Some(user_id) {
+                    *seat = None;
+                    return Ok(None);
+                }
+            }
+        }
+
+        for side in [Side::Left, Side::Right] {
+            // Check main lists, giving the seat to the head of the side's waitlist
+            let seat = self.boats.iter().enumerate().find_map(|(i, boat)| {
+                boat.spots(&side)
+                    .iter()
+                    .position(|id| id.as_ref() == Some(&user_id))
+                    .map(|pos| (i, pos))
+            });
+
+            if let Some((boat, pos)) = seat {
+                let waitlist = self.waitlist_mut(&side);
+                let waitlist_user = waitlist
+                    .iter()
+                    .position(|id| id.is_some())
+                    .and_then(|head| waitlist[head].take());
+
+                self.boats[boat].spots_mut(&side)[pos] = waitlist_user;
+                return Ok(waitlist_user);
+            }
+        }
+
+        for side in [Side::Left, Side::Right] {
+            // Check waitlists
+            let waitlist = self.waitlist_mut(&side);
+            if let Some(pos) = waitlist.iter().position(|id| id.as_ref() == Some(&user_id)) {
+                waitlist[pos] = None;
+                return Ok(None);
+            }
         }
 
         Err(PracticeError::UserNotFound)
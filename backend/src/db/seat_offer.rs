@@ -0,0 +1,38 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SeatOfferStatus {
+    Pending,
+    /// Claimed by an accept that is still writing the practices. The database
+    /// runs as a standalone mongod without multi-document transactions, so a
+    /// swap is two separate writes and this marks offers caught in between.
+    Applying,
+    Accepted,
+    Declined,
+    Cancelled,
+}
+
+/// A member offering their seat to another member. With a `swap_practice_id`
+/// the other member gives up their seat on that practice in exchange.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SeatOffer {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub from_user: ObjectId,
+    pub to_user: ObjectId,
+    pub practice_id: ObjectId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub swap_practice_id: Option<ObjectId>,
+    pub status: SeatOfferStatus,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+impl SeatOffer {
+    pub fn is_pending(&self) -> bool {
+        self.status == SeatOfferStatus::Pending
+    }
+}
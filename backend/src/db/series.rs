@@ -0,0 +1,79 @@
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::America::New_York;
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use super::practice::PracticeType;
+
+/// An inclusive range of dates on which a series doesn't run (e.g. reading week).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DateRange {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+impl DateRange {
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.from <= date && date <= self.to
+    }
+}
+
+/// A recurring practice slot. Times are local to the club (Montreal).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PracticeSeries {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub practice_type: PracticeType,
+    pub weekdays: Vec<Weekday>,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub first_date: NaiveDate,
+    pub last_date: NaiveDate,
+    pub skipped: Vec<DateRange>,
+    pub create_sheet_tabs: bool,
+}
+
+/// A single generated practice of a series.
+pub struct Occurrence {
+    pub date: NaiveDate,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+}
+
+impl PracticeSeries {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.weekdays.is_empty() {
+            return Err("Series must run on at least one weekday".to_string());
+        }
+        if self.end_time <= self.start_time {
+            return Err("Series end time must be after start time".to_string());
+        }
+        if self.last_date < self.first_date {
+            return Err("Series last date must not be before first date".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn occurrences(&self) -> Vec<Occurrence> {
+        self.first_date
+            .iter_days()
+            .take_while(|date| *date <= self.last_date)
+            .filter(|date| self.weekdays.contains(&date.weekday()))
+            .filter(|date| !self.skipped.iter().any(|range| range.contains(*date)))
+            .filter_map(|date| {
+                let start = New_York
+                    .from_local_datetime(&date.and_time(self.start_time))
+                    .earliest()?;
+                let end = New_York
+                    .from_local_datetime(&date.and_time(self.end_time))
+                    .earliest()?;
+
+                Some(Occurrence {
+                    date,
+                    start_time: start.with_timezone(&Utc),
+                    end_time: end.with_timezone(&Utc),
+                })
+            })
+            .collect()
+    }
+}
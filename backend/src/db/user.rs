@@ -1,13 +1,62 @@
+use chrono::{DateTime, Utc};
 use mongodb::bson::oid::ObjectId;
 use serde::{Serialize, Deserialize};
 use std::error::Error;
 
 use crate::sheets::models::FormResponse;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A member's role in the club. Everyone can manage their own signups; roles
+/// add the permissions below.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum UserType {
   Regular,
-  Exec
+  Captain,
+  Exec,
+  Admin
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Permission {
+  /// Sign others up or off, take attendance and pin lineups.
+  EditRosters,
+  /// Create, change and cancel practices and series.
+  SchedulePractices,
+  /// Edit other members' profiles, payments, waivers and penalties.
+  ManageMembers,
+  /// Change club-wide settings.
+  ConfigureClub,
+  /// Grant and revoke roles.
+  GrantRoles
+}
+
+impl UserType {
+    pub fn permissions(&self) -> &'static [Permission] {
+        match self {
+            UserType::Regular => &[],
+            UserType::Captain => &[Permission::EditRosters],
+            UserType::Exec => &[
+                Permission::EditRosters,
+                Permission::SchedulePractices,
+                Permission::ManageMembers,
+            ],
+            UserType::Admin => &[
+                Permission::EditRosters,
+                Permission::SchedulePractices,
+                Permission::ManageMembers,
+                Permission::ConfigureClub,
+                Permission::GrantRoles,
+            ],
+        }
+    }
+
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.permissions().contains(&permission)
+    }
+
+    /// Admins are execs too, e.g. for early access and exec notifications.
+    pub fn is_exec(&self) -> bool {
+        matches!(self, UserType::Exec | UserType::Admin)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -17,13 +66,194 @@ pub enum Gender {
   NA
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Side {
   Left,
   Right,
   NA
 }
 
+/// Special seats in the boat that need a certified member.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BoatRole {
+  Steerer,
+  Drummer
+}
+
+/// Groups of members that can be given early access to signups.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SignupTier {
+  Exec,
+  Competitive
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Experience {
+  Novice,
+  Intermediate,
+  Experienced
+}
+
+impl Experience {
+    /// Maps the free-form answer of the registration form, if recognizable.
+    pub fn from_form(answer: &str) -> Option<Self> {
+        let answer = answer.to_lowercase();
+
+        if ["competitive", "experienced", "advanced", "years"].iter().any(|word| answer.contains(word)) {
+            Some(Experience::Experienced)
+        } else if ["intermediate", "some", "season", "recreational"].iter().any(|word| answer.contains(word)) {
+            Some(Experience::Intermediate)
+        } else if ["never", "none", "beginner", "new", "first"].iter().any(|word| answer.contains(word)) {
+            Some(Experience::Novice)
+        } else {
+            None
+        }
+    }
+}
+
+/// The membership option a member paid for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MembershipPlan {
+  Semester,
+  Season
+}
+
+impl MembershipPlan {
+    /// Maps the membership option picked on the registration form, if recognizable.
+    pub fn from_form(answer: &str) -> Option<Self> {
+        let answer = answer.to_lowercase();
+
+        if ["season", "year", "full"].iter().any(|word| answer.contains(word)) {
+            Some(MembershipPlan::Season)
+        } else if ["semester", "term", "fall", "winter"].iter().any(|word| answer.contains(word)) {
+            Some(MembershipPlan::Semester)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MembershipStatus {
+  /// Registered but the payment hasn't been confirmed by an exec.
+  #[default]
+  Pending,
+  Paid,
+  Expired
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Membership {
+  #[serde(default)]
+  pub status: MembershipStatus,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub plan: Option<MembershipPlan>,
+  /// The member said on the form that they sent an e-transfer.
+  #[serde(default)]
+  pub e_transfer_sent: bool,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub paid_at: Option<DateTime<Utc>>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub expires_at: Option<DateTime<Utc>>
+}
+
+impl Membership {
+    /// Paid memberships lapse on their expiry date even before anyone marks
+    /// them expired.
+    pub fn status_at(&self, now: DateTime<Utc>) -> MembershipStatus {
+        match self.status {
+            MembershipStatus::Paid if self.expires_at.is_some_and(|expires| expires <= now) => {
+                MembershipStatus::Expired
+            }
+            status => status,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WaiverStatus {
+  #[default]
+  Missing,
+  /// Uploaded on the form, waiting for an exec to check it.
+  Submitted,
+  Verified,
+  Rejected
+}
+
+/// The SSMU waiver our insurer requires before anyone goes on the water.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Waiver {
+  #[serde(default)]
+  pub status: WaiverStatus,
+  /// Link to the file uploaded on the registration form.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub file_url: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub reviewed_at: Option<DateTime<Utc>>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub expires_at: Option<DateTime<Utc>>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub rejection_reason: Option<String>
+}
+
+impl Waiver {
+    /// A waiver uploaded on the registration form waits for an exec to check it.
+    pub fn from_form(upload: &str) -> Self {
+        match upload.trim() {
+            "" => Waiver::default(),
+            url => Waiver {
+                status: WaiverStatus::Submitted,
+                file_url: Some(url.to_string()),
+                ..Waiver::default()
+            },
+        }
+    }
+
+    /// Why the member can't sign up yet, or `None` if their waiver is good.
+    pub fn signup_blocker(&self, now: DateTime<Utc>) -> Option<String> {
+        match self.status {
+            WaiverStatus::Missing => Some(
+                "You need to upload a signed SSMU waiver on the registration form before signing up"
+                    .to_string(),
+            ),
+            WaiverStatus::Submitted => {
+                Some("Your waiver hasn't been verified by an exec yet".to_string())
+            }
+            WaiverStatus::Rejected => Some(format!(
+                "Your waiver was rejected ({}), please upload a new one",
+                self.rejection_reason.as_deref().unwrap_or("no reason given")
+            )),
+            WaiverStatus::Verified => self
+                .expires_at
+                .filter(|expires| *expires <= now)
+                .map(|expires| {
+                    format!(
+                        "Your waiver expired on {}, please upload a new one",
+                        expires.format("%B %d, %Y")
+                    )
+                }),
+        }
+    }
+}
+
+/// Which Discord DMs a member gets.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct NotificationSettings {
+  /// Updates about their own signups: promotions, seat offers, cancellations.
+  pub direct_messages: bool,
+  /// Alerts sent to every exec. Only used for execs.
+  pub exec_alerts: bool
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            direct_messages: true,
+            exec_alerts: true,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct User {
   #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
@@ -35,7 +265,28 @@ pub struct User {
   pub mcgill_id: String,
   pub email: String,
   pub user_type : UserType,
-  pub side: Side
+  pub side: Side,
+  #[serde(default)]
+  pub qualifications: Vec<BoatRole>,
+  #[serde(default)]
+  pub weight_kg: Option<f32>,
+  #[serde(default)]
+  pub experience: Option<Experience>,
+  /// On the competitive team's roster this season.
+  #[serde(default)]
+  pub competitive_roster: bool,
+  /// No-show penalties are ignored until then, set by an exec.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub penalty_waived_until: Option<DateTime<Utc>>,
+  #[serde(default)]
+  pub membership: Membership,
+  #[serde(default)]
+  pub waiver: Waiver,
+  /// Shown in lineups and messages instead of the name from the form.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub display_name: Option<String>,
+  #[serde(default)]
+  pub notifications: NotificationSettings
 }
 
 impl User {
@@ -67,7 +318,123 @@ impl User {
             mcgill_id: form.mcgill_id.clone(),
             user_type,
             side,
-            email
+            email,
+            qualifications: Vec::new(),
+            weight_kg: None,
+            experience: Experience::from_form(&form.experience_level),
+            competitive_roster: false,
+            penalty_waived_until: None,
+            membership: Membership {
+                plan: MembershipPlan::from_form(&form.membership_option),
+                e_transfer_sent: form.e_transfer_sent,
+                ..Membership::default()
+            },
+            waiver: Waiver::from_form(&form.waiver_upload),
+            display_name: None,
+            notifications: NotificationSettings::default(),
         })
     }
+
+    /// Fills in form answers the member doesn't have yet, returning whether
+    /// anything changed. Anything an exec already reviewed is kept.
+    pub fn backfill_from_form(&mut self, form: &FormResponse) -> bool {
+        let mut changed = false;
+
+        if self.waiver.status == WaiverStatus::Missing && self.waiver.file_url.is_none() {
+            let waiver = Waiver::from_form(&form.waiver_upload);
+            if waiver.file_url.is_some() {
+                self.waiver = waiver;
+                changed = true;
+            }
+        }
+        if self.membership.plan.is_none() {
+            self.membership.plan = MembershipPlan::from_form(&form.membership_option);
+            changed |= self.membership.plan.is_some();
+        }
+        if form.e_transfer_sent && !self.membership.e_transfer_sent {
+            self.membership.e_transfer_sent = true;
+            changed = true;
+        }
+
+        changed
+    }
+
+    /// The name other members see.
+    pub fn name(&self) -> String {
+        self.display_name
+            .clone()
+            .unwrap_or_else(|| format!("{} {}", self.first_name, self.last_name))
+    }
+
+    pub fn signup_tiers(&self) -> Vec<SignupTier> {
+        let mut tiers = Vec::new();
+
+        if self.user_type.is_exec() {
+            tiers.push(SignupTier::Exec);
+        }
+        if self.competitive_roster {
+            tiers.push(SignupTier::Competitive);
+        }
+
+        tiers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn waiver(status: WaiverStatus) -> Waiver {
+        Waiver {
+            status,
+            ..Waiver::default()
+        }
+    }
+
+    #[test]
+    fn only_verified_waivers_allow_signups() {
+        let now = Utc::now();
+
+        assert!(waiver(WaiverStatus::Missing).signup_blocker(now).is_some());
+        assert!(waiver(WaiverStatus::Submitted).signup_blocker(now).is_some());
+        assert!(waiver(WaiverStatus::Verified).signup_blocker(now).is_none());
+    }
+
+    #[test]
+    fn rejected_waivers_give_the_reason() {
+        let rejected = Waiver {
+            rejection_reason: Some("unsigned".to_string()),
+            ..waiver(WaiverStatus::Rejected)
+        };
+
+        let message = rejected.signup_blocker(Utc::now()).unwrap();
+        assert!(message.contains("unsigned"));
+    }
+
+    #[test]
+    fn verified_waivers_expire() {
+        let now = Utc::now();
+
+        let current = Waiver {
+            expires_at: Some(now + Duration::days(1)),
+            ..waiver(WaiverStatus::Verified)
+        };
+        assert!(current.signup_blocker(now).is_none());
+
+        let expired = Waiver {
+            expires_at: Some(now),
+            ..waiver(WaiverStatus::Verified)
+        };
+        assert!(expired.signup_blocker(now).unwrap().contains("expired"));
+    }
+
+    #[test]
+    fn form_uploads_wait_for_review() {
+        assert_eq!(Waiver::from_form("  ").status, WaiverStatus::Missing);
+
+        let uploaded = Waiver::from_form("https://drive.google.com/open?id=abc");
+        assert_eq!(uploaded.status, WaiverStatus::Submitted);
+        assert!(uploaded.signup_blocker(Utc::now()).is_some());
+    }
 }
@@ -1,61 +1,154 @@
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Utc};
+use chrono_tz::America::New_York;
 use mongodb::bson::oid::ObjectId;
 use reqwest::Client as HttpClient;
-use std::{error::Error, sync::Arc, time::Duration as StdDuration};
+use std::{error::Error, future::Future, sync::Arc};
 use tokio_cron_scheduler::{Job, JobScheduler};
 use tracing::info;
+use uuid::Uuid;
 
 use crate::db::db::DB;
+use crate::db::attendance::AttendanceStatus;
+use crate::db::audit::{AuditAction, RosterChange};
+use crate::db::club::SignupEligibility;
+use crate::db::lottery::{Lottery, LotteryDraw, LotteryEntry, LotteryWeighting, WEIGHTING_WINDOW_DAYS};
 use crate::db::practice::Practice;
+use crate::db::user::SignupTier;
+use crate::notifications::{notify_execs, notify_users};
 use crate::router::responses::{PracticeStartInfo, WaitlistTransferNotification};
 
+/// How many times a job reads a practice again when a member changed it
+/// while the job was working on it.
+const MAX_WRITE_ATTEMPTS: usize = 3;
+
+/// How long before signups close execs are warned about a missing steerer.
+const ROLE_CHECK_LEAD: chrono::Duration = chrono::Duration::hours(2);
+
 pub async fn schedule_practice_jobs(
     practice: &Practice,
     scheduler: &JobScheduler,
     db: Arc<DB>,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<Vec<Uuid>, Box<dyn Error>> {
     let practice_id = practice.id.unwrap();
-    let waitlist_transfer_time =
-        practice.start_time - chrono::Duration::hours(1) - chrono::Duration::seconds(30);
+    let mut job_ids = Vec::new();
 
-    let unlock_time = practice.start_time - chrono::Duration::hours(1);
-    let now = Utc::now();
-
-    // Only schedule if times are in the future
-    if waitlist_transfer_time > now {
-        info!(
-            "Creating job for waitlist transfer @ {}",
-            waitlist_transfer_time
+    if practice.practice_type.carries_over_waitlist() {
+        job_ids.extend(
+            schedule_job(
+                scheduler,
+                db.clone(),
+                practice_id,
+                practice.waitlist_transfer_at,
+                "waitlist transfer",
+                handle_waitlist_transfer,
+            )
+            .await?,
         );
-        schedule_waitlist_transfer(scheduler, db.clone(), practice_id, waitlist_transfer_time)
-            .await?;
     } else {
         info!(
-            "Skipping waitlist transfer job as time {} has passed",
-            waitlist_transfer_time
+            "Skipping waitlist transfer job as {:?} practices don't carry over waitlists",
+            practice.practice_type
         );
     }
 
-    if unlock_time > now {
-        info!("Creating job for unlock time transfer @ {}", unlock_time);
-        schedule_practice_unlock(scheduler, db.clone(), practice_id, unlock_time).await?;
-    } else {
-        info!(
-            "Skipping unlock time job as time {} has passed",
-            unlock_time
+    // The signup post goes up as soon as the first tier can use it
+    job_ids.extend(
+        schedule_job(
+            scheduler,
+            db.clone(),
+            practice_id,
+            practice.earliest_signup_open(),
+            "unlock",
+            notify_practice_unlock,
+        )
+        .await?,
+    );
+
+    for early in &practice.early_access {
+        let tier = early.tier;
+        job_ids.extend(
+            schedule_job(
+                scheduler,
+                db.clone(),
+                practice_id,
+                early.opens_at(practice.signup_opens_at),
+                "early access",
+                move |db, practice_id| notify_early_access(db, practice_id, tier),
+            )
+            .await?,
         );
     }
 
-    Ok(())
+    if let Some(lottery) = practice.lottery.as_ref().filter(|lottery| lottery.is_open()) {
+        // A draw missed while the server was down still has to happen
+        let draw_time = lottery
+            .draws_at
+            .max(Utc::now() + chrono::Duration::seconds(5));
+        job_ids.extend(
+            schedule_job(
+                scheduler,
+                db.clone(),
+                practice_id,
+                draw_time,
+                "lottery draw",
+                handle_lottery_draw,
+            )
+            .await?,
+        );
+    }
+
+    if practice.practice_type.has_boat_roles() {
+        let role_check_time =
+            (practice.signup_closes_at - ROLE_CHECK_LEAD).max(practice.signup_opens_at);
+        job_ids.extend(
+            schedule_job(
+                scheduler,
+                db.clone(),
+                practice_id,
+                role_check_time,
+                "boat role check",
+                check_boat_roles,
+            )
+            .await?,
+        );
+    }
+
+    job_ids.extend(
+        schedule_job(
+            scheduler,
+            db.clone(),
+            practice_id,
+            practice.signup_closes_at,
+            "signup close",
+            handle_signup_close,
+        )
+        .await?,
+    );
+
+    Ok(job_ids)
 }
 
-async fn schedule_waitlist_transfer(
+/// Schedules a one-shot job running `handler` for a practice. Returns `None`
+/// without scheduling anything if the time has already passed.
+async fn schedule_job<F, Fut>(
     scheduler: &JobScheduler,
     db: Arc<DB>,
     practice_id: ObjectId,
     execution_time: DateTime<Utc>,
-) -> Result<(), Box<dyn Error>> {
-    scheduler
+    name: &'static str,
+    handler: F,
+) -> Result<Option<Uuid>, Box<dyn Error>>
+where
+    F: Fn(Arc<DB>, ObjectId) -> Fut + Send + Sync + Copy + 'static,
+    Fut: Future<Output = Result<(), Box<dyn Error + Send + Sync>>> + Send + 'static,
+{
+    if execution_time <= Utc::now() {
+        info!("Skipping {} job as time {} has passed", name, execution_time);
+        return Ok(None);
+    }
+
+    info!("Creating job for {} @ {}", name, execution_time);
+    let job_id = scheduler
         .add(
             Job::new_one_shot_async(
                 execution_time
@@ -64,11 +157,10 @@ async fn schedule_waitlist_transfer(
                     .map_err(|_| format!("Target time is in the past {}", execution_time))?,
                 move |_uuid, _l| {
                     let db = db.clone();
-                    let practice_id = practice_id.clone();
                     Box::pin(async move {
-                        info!("Executing waitlist transfer for practice {}", practice_id);
-                        if let Err(e) = handle_waitlist_transfer(db, practice_id).await {
-                            tracing::error!("Waitlist transfer failed: {}", e);
+                        info!("Executing {} for practice {}", name, practice_id);
+                        if let Err(e) = handler(db, practice_id).await {
+                            tracing::error!("{} failed for practice {}: {}", name, practice_id, e);
                         }
                     })
                 },
@@ -76,36 +168,118 @@ async fn schedule_waitlist_transfer(
             .unwrap(),
         )
         .await?;
+
+    Ok(Some(job_id))
+}
+
+/// Warns execs ahead of signup close when nobody qualified has claimed the
+/// steerer seat of a boat.
+async fn check_boat_roles(
+    db: Arc<DB>,
+    practice_id: ObjectId,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(practice) = db.get_practice(practice_id).await? else {
+        return Ok(());
+    };
+
+    if practice.is_cancelled() {
+        return Ok(());
+    }
+
+    let missing = practice
+        .boats
+        .iter()
+        .filter(|boat| boat.steerer.is_none())
+        .map(|boat| match boat.drummer {
+            Some(_) => format!("steerer in {}", boat.name),
+            None => format!("steerer or drummer in {}", boat.name),
+        })
+        .collect::<Vec<_>>();
+
+    if missing.is_empty() {
+        info!("Every boat of practice {} has a steerer", practice_id);
+        return Ok(());
+    }
+
+    notify_execs(
+        &db,
+        &format!(
+            "The practice on {} has no {} yet and signups close on {}",
+            practice
+                .start_time
+                .with_timezone(&New_York)
+                .format("%A, %B %d at %I:%M %p"),
+            missing.join(", "),
+            practice
+                .signup_closes_at
+                .with_timezone(&New_York)
+                .format("%A, %B %d at %I:%M %p")
+        ),
+    )
+    .await;
+
     Ok(())
 }
 
-async fn schedule_practice_unlock(
-    scheduler: &JobScheduler,
+/// Finalizes the roster once signups close: flex paddlers are rebalanced
+/// across sides and told which side they ended up on.
+async fn handle_signup_close(
     db: Arc<DB>,
     practice_id: ObjectId,
-    execution_time: DateTime<Utc>,
-) -> Result<(), Box<dyn Error>> {
-    scheduler
-        .add(
-            Job::new_one_shot_async(
-                execution_time
-                    .signed_duration_since(Utc::now())
-                    .to_std()
-                    .map_err(|_| format!("Target time is in the past {}", execution_time))?,
-                move |_uuid, _l| {
-                    let db = db.clone();
-                    let practice_id = practice_id.clone();
-                    Box::pin(async move {
-                        info!("Notifying practice unlock for practice {}", practice_id);
-                        if let Err(e) = notify_practice_unlock(db, practice_id).await {
-                            eprintln!("Error notifying practice unlock: {}", e);
-                        }
-                    })
-                },
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let change = RosterChange::job(AuditAction::Rebalance);
+    let mut attempts = 0;
+    let (practice, report) = loop {
+        let Some(mut practice) = db.get_practice(practice_id).await? else {
+            return Ok(());
+        };
+
+        if practice.is_cancelled() {
+            info!("Skipping signup close for cancelled practice {}", practice_id);
+            return Ok(());
+        }
+
+        let report = practice.rebalance_flex();
+        if db.update_roster(&mut practice, &change).await? {
+            break (practice, report);
+        }
+
+        attempts += 1;
+        if attempts == MAX_WRITE_ATTEMPTS {
+            return Err(format!("Practice {} kept changing while signups closed", practice_id).into());
+        }
+        info!("Practice {} changed while rebalancing, rebalancing again", practice_id);
+    };
+    info!("Rebalanced flex paddlers for practice {}: {:?}", practice_id, report);
+
+    let practice_day = practice
+        .start_time
+        .with_timezone(&New_York)
+        .format("%A, %B %d at %I:%M %p");
+
+    for user_id in practice.flex_paddlers.clone() {
+        if let Some(side) = practice.seated_side(user_id) {
+            notify_users(
+                &db,
+                [user_id],
+                &format!(
+                    "You'll be paddling on the {:?} side for the practice on {}",
+                    side, practice_day
+                ),
             )
-            .unwrap(),
-        )
-        .await?;
+            .await;
+        }
+    }
+
+    notify_users(
+        &db,
+        report.promoted.iter().copied(),
+        &format!(
+            "A seat opened up, you're now on the main list for the practice on {}",
+            practice_day
+        ),
+    )
+    .await;
 
     Ok(())
 }
@@ -115,69 +289,389 @@ async fn handle_waitlist_transfer(
     practice_id: ObjectId,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
     if let Some(mut practice) = db.get_practice(practice_id).await? {
+        if practice.is_cancelled() {
+            info!("Skipping waitlist transfer for cancelled practice {}", practice_id);
+            return Ok(());
+        }
+
         info!(
             "Processing waitlist transfer for practice on {}",
             practice.date
         );
 
-        if let Some(previous_practice) = db.get_previous_practice(&practice).await? {
-            let all_waitlist_users = previous_practice
-                .left_side
-                .iter()
-                .chain(previous_practice.right_side.iter())
-                .filter_map(|id| id.as_ref())
-                .collect::<Vec<_>>();
+        if !practice.practice_type.carries_over_waitlist() {
+            info!("{:?} practices don't carry over waitlists", practice.practice_type);
+            return Ok(());
+        }
+
+        let change = RosterChange::job(AuditAction::WaitlistCarryOver);
+        for previous_practice in db.get_waitlist_sources(&practice).await? {
+            let mut attempts = 0;
+            let report = loop {
+                let report =
+                    practice.transfer_waitlist(&previous_practice, &previous_practice.flex_paddlers);
+                if db.update_roster(&mut practice, &change).await? {
+                    break report;
+                }
 
-            practice.transfer_waitlist(&previous_practice);
-            db.update_practice(&practice).await?;
+                attempts += 1;
+                if attempts == MAX_WRITE_ATTEMPTS {
+                    return Err(format!(
+                        "Practice {} kept changing during its waitlist transfer",
+                        practice_id
+                    )
+                    .into());
+                }
+                info!("Practice {} changed during its waitlist transfer, transferring again", practice_id);
+                practice = db.get_practice(practice_id).await?.ok_or("Practice not found")?;
+                if practice.is_cancelled() {
+                    info!("Practice {} was cancelled during its waitlist transfer", practice_id);
+                    return Ok(());
+                }
+            };
+
+            info!(
+                "Waitlist transfer from practice {:?} into {}: {:?}",
+                previous_practice.id, practice_id, report
+            );
 
             let client = HttpClient::new();
 
-            for user_id in all_waitlist_users {
-              if let Some(user) = db.get_user(*user_id).await? {
-                if let Some(discord_id) = user.discord_id {
-                  let practice_info = PracticeStartInfo {
-                    practice_id: practice.id.unwrap().to_string(),
-                    start_time: practice.start_time,
-                    end_time: practice.end_time
-                  };
-
-                  let notification = WaitlistTransferNotification {
-                    practice: practice_info,
-                    discord_id : discord_id.clone()
-                  };
-
-                  info!(
-                      "Sending waitlist notification for user {} {} : {} for practice {:?}",
-                      user.first_name,
-                      user.last_name,
-                      discord_id,
-                      practice.id
-                  );
-
-                  let response = client.post("http://discord-bot:3001/waitlisted-msg")
+            for user_id in &report.placed {
+                let Some(user) = db.get_user(*user_id).await? else {
+                    continue;
+                };
+                let Some(discord_id) = user
+                    .discord_id
+                    .filter(|_| user.notifications.direct_messages)
+                else {
+                    continue;
+                };
+
+                let notification = WaitlistTransferNotification {
+                    practice: PracticeStartInfo::from(&practice),
+                    discord_id: discord_id.clone(),
+                };
+
+                info!(
+                    "Sending waitlist notification for user {} {} : {} for practice {:?}",
+                    user.first_name, user.last_name, discord_id, practice.id
+                );
+
+                let response = match client
+                    .post("http://discord-bot:3001/waitlisted-msg")
                     .json(&notification)
                     .send()
-                    .await?;
-
-                  if !response.status().is_success() {
-                                                  info!(
-                                                      "Failed to send waitlist notification for user {}: {}",
-                                                      discord_id,
-                                                      response.status()
-                                                  );
-                                              }
+                    .await
+                {
+                    Ok(response) => response,
+                    Err(e) => {
+                        info!(
+                            "Failed to send waitlist notification for user {}: {}",
+                            discord_id, e
+                        );
+                        continue;
+                    }
+                };
+
+                if !response.status().is_success() {
+                    info!(
+                        "Failed to send waitlist notification for user {}: {}",
+                        discord_id,
+                        response.status()
+                    );
                 }
-              }
             }
 
-            info!(
-                "Successfully transferred waitlist for practice {}",
-                practice.id.unwrap()
-            );
+            let practice_day = practice
+                .start_time
+                .with_timezone(&New_York)
+                .format("%A, %B %d at %I:%M %p");
+
+            notify_users(
+                &db,
+                report.waitlisted.iter().copied(),
+                &format!(
+                    "You were on last practice's waitlist, so you're at the top of the waitlist for the practice on {}",
+                    practice_day
+                ),
+            )
+            .await;
+
+            notify_users(
+                &db,
+                report.dropped.iter().copied(),
+                &format!(
+                    "You were on last practice's waitlist, but the practice on {} and its waitlist are already full",
+                    practice_day
+                ),
+            )
+            .await;
+
+            notify_execs(
+                &db,
+                &format!(
+                    "Waitlist carry-over for the practice on {}: {}",
+                    practice_day,
+                    report.summary()
+                ),
+            )
+            .await;
+        }
+
+        info!(
+            "Successfully transferred waitlist for practice {}",
+            practice_id
+        );
+    }
+
+    Ok(())
+}
+
+/// Draws the lottery of a practice and tells every entrant how they fared.
+/// Members keep entering and leaving until the draw is written, so a practice
+/// that changed in the meantime is read again and drawn anew.
+async fn handle_lottery_draw(
+    db: Arc<DB>,
+    practice_id: ObjectId,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let change = RosterChange::job(AuditAction::LotteryDraw);
+    let mut attempts = 0;
+    let (practice, DrawOutcome { draw, refused, seed }) = loop {
+        let Some(mut practice) = db.get_practice(practice_id).await? else {
+            return Ok(());
+        };
+
+        if practice.is_cancelled() {
+            info!("Skipping lottery draw for cancelled practice {}", practice_id);
+            return Ok(());
+        }
+
+        let Some(outcome) = draw_lottery(&db, &mut practice).await? else {
+            return Ok(());
+        };
+        if db.update_roster(&mut practice, &change).await? {
+            break (practice, outcome);
+        }
+
+        attempts += 1;
+        if attempts == MAX_WRITE_ATTEMPTS {
+            return Err(format!("Practice {} kept changing during its lottery draw", practice_id).into());
+        }
+        info!("Practice {} changed during its lottery draw, drawing again", practice_id);
+    };
+    info!(
+        "Drew lottery for practice {} with seed {}: {} seated, {} waitlisted, {} dropped",
+        practice_id,
+        seed,
+        draw.seated.len(),
+        draw.waitlisted.len(),
+        draw.dropped.len()
+    );
+
+    let practice_day = practice
+        .start_time
+        .with_timezone(&New_York)
+        .format("%A, %B %d at %I:%M %p");
+
+    for (user_id, message) in refused {
+        info!("Took {} out of the lottery for practice {}: {}", user_id, practice_id, message);
+        notify_users(
+            &db,
+            [user_id],
+            &format!(
+                "You were taken out of the lottery for the practice on {}: {}",
+                practice_day, message
+            ),
+        )
+        .await;
+    }
+
+    notify_users(
+        &db,
+        draw.seated.iter().copied(),
+        &format!("You got a seat in the lottery for the practice on {}!", practice_day),
+    )
+    .await;
+    notify_users(
+        &db,
+        draw.waitlisted.iter().copied(),
+        &format!(
+            "You weren't drawn for a seat at the practice on {}, you're on the waitlist",
+            practice_day
+        ),
+    )
+    .await;
+    notify_users(
+        &db,
+        draw.dropped.iter().copied(),
+        &format!(
+            "You weren't drawn for a seat at the practice on {} and the waitlist is full",
+            practice_day
+        ),
+    )
+    .await;
+
+    notify_execs(
+        &db,
+        &format!(
+            "Lottery for the practice on {} drawn with seed {}: {} seated, {} waitlisted, {} dropped",
+            practice_day,
+            seed,
+            draw.seated.len(),
+            draw.waitlisted.len(),
+            draw.dropped.len()
+        ),
+    )
+    .await;
+
+    Ok(())
+}
+
+/// A drawn lottery, with the members taken out of it before the draw.
+struct DrawOutcome {
+    draw: LotteryDraw,
+    refused: Vec<(ObjectId, String)>,
+    seed: i64,
+}
+
+/// Checks the entries of an open lottery again and draws it on `practice`,
+/// without writing it.
+async fn draw_lottery(
+    db: &DB,
+    practice: &mut Practice,
+) -> Result<Option<DrawOutcome>, Box<dyn Error + Send + Sync>> {
+    let Some(lottery) = practice.lottery.as_ref().filter(|lottery| lottery.is_open()) else {
+        return Ok(None);
+    };
+
+    // Penalties may have changed since members entered, so check everyone
+    // again as if they were signing up now
+    let settings = db.get_club_settings().await?;
+    let now = Utc::now();
+    let mut entries = Vec::new();
+    let mut refused = Vec::new();
+    for entry in &lottery.entries {
+        let Some(user) = db.get_user(entry.user_id).await? else {
+            entries.push(entry.clone());
+            continue;
+        };
+        let history = db
+            .get_user_attendance(entry.user_id)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        // Paying since entering lifts a waitlist-only entry, lapsing adds one
+        match settings.signup_eligibility(
+            &user,
+            &history,
+            practice.signup_opens_for(&user.signup_tiers()),
+            now,
+        ) {
+            SignupEligibility::Refused(message) => refused.push((entry.user_id, message)),
+            eligibility => entries.push(LotteryEntry {
+                waitlist_only: matches!(eligibility, SignupEligibility::WaitlistOnly(_)),
+                ..entry.clone()
+            }),
         }
     }
 
+    let Some(lottery) = practice.lottery.as_mut() else {
+        return Ok(None);
+    };
+    lottery.entries = entries;
+    let lottery = &*lottery;
+
+    let since = Utc::now() - chrono::Duration::days(WEIGHTING_WINDOW_DAYS);
+    let entrants = lottery
+        .entries
+        .iter()
+        .map(|entry| entry.user_id)
+        .collect::<Vec<_>>();
+
+    let weights = match lottery.weighting {
+        LotteryWeighting::Equal => vec![1.0; entrants.len()],
+        LotteryWeighting::RecentAttendance => {
+            let mut weights = Vec::new();
+            for user_id in &entrants {
+                let attended = db
+                    .get_user_attendance(*user_id)
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .into_iter()
+                    .filter(|record| record.practice_start >= since)
+                    .filter(|record| {
+                        matches!(
+                            record.status,
+                            AttendanceStatus::Present | AttendanceStatus::Late
+                        )
+                    })
+                    .count();
+                weights.push(1.0 + attended as f64);
+            }
+            weights
+        }
+        LotteryWeighting::MissedPractices => {
+            let recent = db.get_drawn_lotteries_since(since).await?;
+            entrants
+                .iter()
+                .map(|user_id| {
+                    let missed = recent
+                        .iter()
+                        .filter_map(|practice| practice.lottery.as_ref()?.draw.as_ref())
+                        .filter(|draw| {
+                            draw.waitlisted.contains(user_id) || draw.dropped.contains(user_id)
+                        })
+                        .count();
+                    1.0 + missed as f64
+                })
+                .collect()
+        }
+    };
+
+    let seed = Lottery::new_seed();
+    Ok(practice
+        .run_lottery(weights, seed)
+        .cloned()
+        .map(|draw| DrawOutcome { draw, refused, seed }))
+}
+
+/// Tells the members of a tier that signups are open for them ahead of
+/// everyone else.
+async fn notify_early_access(
+    db: Arc<DB>,
+    practice_id: ObjectId,
+    tier: SignupTier,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let Some(practice) = db.get_practice(practice_id).await? else {
+        return Ok(());
+    };
+
+    if practice.is_cancelled() {
+        info!("Skipping early access notification for cancelled practice {}", practice_id);
+        return Ok(());
+    }
+
+    let members = db
+        .get_tier_members(tier)
+        .await?
+        .into_iter()
+        .filter_map(|user| user.id)
+        .collect::<Vec<_>>();
+
+    let message = format!(
+        "Signups for the practice on {} are open early for you! Everyone else can sign up from {}",
+        practice
+            .start_time
+            .with_timezone(&New_York)
+            .format("%A, %B %d at %I:%M %p"),
+        practice
+            .signup_opens_at
+            .with_timezone(&New_York)
+            .format("%A, %B %d at %I:%M %p")
+    );
+    notify_users(&db, members, &message).await;
+
     Ok(())
 }
 
@@ -186,12 +680,13 @@ async fn notify_practice_unlock(
     practice_id: ObjectId,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
     if let Some(practice) = db.get_practice(practice_id).await? {
+        if practice.is_cancelled() {
+            info!("Skipping unlock notification for cancelled practice {}", practice_id);
+            return Ok(());
+        }
+
         let client = HttpClient::new();
-        let practice_info = PracticeStartInfo {
-            practice_id: practice.id.unwrap().to_string(),
-            start_time: practice.start_time,
-            end_time: practice.end_time,
-        };
+        let practice_info = PracticeStartInfo::from(&practice);
 
         let response = client
             .post("http://discord-bot:3001/practice")
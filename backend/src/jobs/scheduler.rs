@@ -1,21 +1,26 @@
-use std::{error::Error, sync::Arc};
+use mongodb::bson::oid::ObjectId;
+use std::{collections::HashMap, error::Error, sync::Arc};
+use tokio::sync::Mutex;
 use tokio_cron_scheduler::JobScheduler;
 use tracing::info;
+use uuid::Uuid;
 
+use crate::db::practice::Practice;
 use crate::jobs::practice::schedule_practice_jobs;
 use crate::DB;
 use crate::sheets::sheets::SheetsClient;
 
 pub struct SchedulerManager {
   scheduler: JobScheduler,
-  db: Arc<DB>
+  db: Arc<DB>,
+  practice_jobs: Mutex<HashMap<ObjectId, Vec<Uuid>>>
 }
 
 impl SchedulerManager {
   pub async fn new(db: Arc<DB>) -> Result<Self, Box<dyn Error>> {
     let scheduler = JobScheduler::new().await?;
 
-    Ok(Self{scheduler, db})
+    Ok(Self{scheduler, db, practice_jobs: Mutex::new(HashMap::new())})
   }
 
   pub async fn init_jobs(&self, practice_client: Arc<SheetsClient>) -> Result<(), Box<dyn Error>> {
@@ -26,7 +31,7 @@ impl SchedulerManager {
 
     for practice in practices {
       if practice.is_future() {
-        schedule_practice_jobs(&practice, &self.scheduler, self.db.clone()).await?;
+        self.schedule_practice(&practice).await?;
       }
     }
     self.scheduler.start().await?;
@@ -34,7 +39,23 @@ impl SchedulerManager {
     Ok(())
   }
 
-  pub fn get_scheduler(&self) -> &JobScheduler {
-    &self.scheduler
+  /// Schedules the jobs of a practice, replacing any previously scheduled for it.
+  pub async fn schedule_practice(&self, practice: &Practice) -> Result<(), Box<dyn Error>> {
+    let practice_id = practice.id.ok_or("Practice has no ID")?;
+    self.unschedule_practice(practice_id).await?;
+
+    let job_ids = schedule_practice_jobs(practice, &self.scheduler, self.db.clone()).await?;
+    self.practice_jobs.lock().await.insert(practice_id, job_ids);
+    Ok(())
+  }
+
+  pub async fn unschedule_practice(&self, practice_id: ObjectId) -> Result<(), Box<dyn Error>> {
+    let job_ids = self.practice_jobs.lock().await.remove(&practice_id);
+
+    for job_id in job_ids.unwrap_or_default() {
+      info!("Removing job {} for practice {}", job_id, practice_id);
+      self.scheduler.remove(&job_id).await?;
+    }
+    Ok(())
   }
 }
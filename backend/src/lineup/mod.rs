@@ -0,0 +1,561 @@
+use mongodb::bson::oid::ObjectId;
+use serde::Serialize;
+use std::{cmp::Reverse, collections::HashMap};
+
+use crate::db::{
+    practice::{Boat, LineupOverride, Practice},
+    user::{Experience, Side, User},
+};
+
+/// Paddling rows in a standard boat, row 1 being at the front.
+pub const ROWS: usize = 10;
+
+/// Order in which rows are filled: the stroke pair sets the pace and the
+/// engine room carries the power, so they get the most experienced paddlers.
+const ROW_PRIORITY: [usize; ROWS] = [1, 2, 4, 5, 6, 7, 3, 8, 9, 10];
+
+/// Used for paddlers without a weight when no one else has one either.
+const FALLBACK_WEIGHT_KG: f32 = 70.0;
+
+/// The balance point of the boat sits between rows 5 and 6.
+const CENTER_ROW: f32 = 5.5;
+const DRUMMER_ROW: f32 = 0.0;
+const STEERER_ROW: f32 = 11.0;
+
+/// Improvements smaller than this are ignored so the search always settles.
+const EPSILON: f32 = 0.01;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Seat {
+    pub user_id: ObjectId,
+    pub name: String,
+    pub weight_kg: Option<f32>,
+    pub experience: Option<Experience>,
+    pub pinned: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LineupRow {
+    pub row: usize,
+    pub left: Option<Seat>,
+    pub right: Option<Seat>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Lineup {
+    pub boat: String,
+    pub drummer: Option<Seat>,
+    pub rows: Vec<LineupRow>,
+    pub steerer: Option<Seat>,
+    /// Paddlers on the roster who didn't fit in the boat.
+    pub alternates: Vec<Seat>,
+    /// Left total minus right total, in kg.
+    pub left_right_kg: f32,
+    /// Weight times distance from the center row; positive when back-heavy.
+    pub front_back_moment: f32,
+}
+
+impl Lineup {
+    /// Clears every paddler's weight except `viewer`'s. The balance totals
+    /// stay, they don't give anyone's weight away.
+    pub fn hide_weights(&mut self, viewer: Option<ObjectId>) {
+        let seats = self
+            .rows
+            .iter_mut()
+            .flat_map(|row| [&mut row.left, &mut row.right])
+            .chain([&mut self.drummer, &mut self.steerer])
+            .flatten()
+            .chain(self.alternates.iter_mut());
+
+        for seat in seats {
+            if Some(seat.user_id) != viewer {
+                seat.weight_kg = None;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Placed {
+    user_id: ObjectId,
+    pinned: bool,
+}
+
+/// Seats indexed by `[row - 1][side]`, with the left side at index 0.
+type Grid = [[Option<Placed>; 2]; ROWS];
+
+fn side_index(side: &Side) -> Option<usize> {
+    match side {
+        Side::Left => Some(0),
+        Side::Right => Some(1),
+        Side::NA => None,
+    }
+}
+
+/// Checks that overrides only pin paddlers to a real seat in the boat they're
+/// seated in, and only move flex paddlers to the other side.
+pub fn validate_overrides(practice: &Practice, overrides: &[LineupOverride]) -> Result<(), String> {
+    for (i, pinned) in overrides.iter().enumerate() {
+        if pinned.row == 0 || pinned.row > ROWS {
+            return Err(format!("Row must be between 1 and {}", ROWS));
+        }
+
+        let (boat, seated_side) = practice
+            .seat_of(pinned.user_id)
+            .ok_or(format!("User {} has no seat on this practice", pinned.user_id))?;
+
+        if pinned.boat != boat {
+            return Err(format!(
+                "User {} is seated in {}",
+                pinned.user_id, practice.boats[boat].name
+            ));
+        }
+
+        if side_index(&pinned.side).is_none() {
+            return Err("Pinned side must be Left or Right".to_string());
+        }
+        if pinned.side != seated_side && !practice.flex_paddlers.contains(&pinned.user_id) {
+            return Err(format!("User {} can only paddle {:?}", pinned.user_id, seated_side));
+        }
+
+        for other in &overrides[..i] {
+            if other.user_id == pinned.user_id {
+                return Err(format!("User {} is pinned twice", pinned.user_id));
+            }
+            if other.boat == pinned.boat && other.row == pinned.row && other.side == pinned.side {
+                return Err(format!("Row {} {:?} is pinned twice", pinned.row, pinned.side));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Assigns the paddlers seated in one boat of a practice to rows. Pinned seats
+/// are placed first, the rest fill rows by experience then weight, and
+/// same-level paddlers are swapped around to even out the boat.
+pub fn build_lineup(practice: &Practice, boat: usize, users: &HashMap<ObjectId, User>) -> Lineup {
+    let index = boat;
+    let boat = &practice.boats[index];
+
+    let known_weights = users
+        .values()
+        .filter_map(|user| user.weight_kg)
+        .collect::<Vec<_>>();
+    let default_weight = if known_weights.is_empty() {
+        FALLBACK_WEIGHT_KG
+    } else {
+        known_weights.iter().sum::<f32>() / known_weights.len() as f32
+    };
+
+    let builder = Builder {
+        users,
+        flex: &practice.flex_paddlers,
+        default_weight,
+    };
+
+    let mut pools = [
+        boat.left_side.iter().flatten().copied().collect::<Vec<_>>(),
+        boat.right_side.iter().flatten().copied().collect::<Vec<_>>(),
+    ];
+    let mut grid: Grid = [[None; 2]; ROWS];
+
+    for pinned in practice
+        .lineup_overrides
+        .iter()
+        .filter(|pinned| pinned.boat == index)
+    {
+        let Some(side) = side_index(&pinned.side) else {
+            continue;
+        };
+        let seat = match grid.get_mut(pinned.row.wrapping_sub(1)) {
+            Some(row) if row[side].is_none() => &mut row[side],
+            _ => continue,
+        };
+
+        for pool in pools.iter_mut() {
+            if let Some(pos) = pool.iter().position(|id| *id == pinned.user_id) {
+                pool.remove(pos);
+                *seat = Some(Placed {
+                    user_id: pinned.user_id,
+                    pinned: true,
+                });
+                break;
+            }
+        }
+    }
+
+    let mut alternates = Vec::new();
+    for (side, pool) in pools.iter_mut().enumerate() {
+        pool.sort_by(|a, b| {
+            Reverse(builder.experience(a))
+                .cmp(&Reverse(builder.experience(b)))
+                .then(builder.weight(b).total_cmp(&builder.weight(a)))
+        });
+
+        let mut paddlers = pool.drain(..);
+        for row in ROW_PRIORITY {
+            if grid[row - 1][side].is_none() {
+                grid[row - 1][side] = paddlers.next().map(|user_id| Placed {
+                    user_id,
+                    pinned: false,
+                });
+            }
+        }
+        alternates.extend(paddlers);
+    }
+
+    builder.balance_front_back(&mut grid, boat);
+    builder.balance_sides(&mut grid);
+
+    Lineup {
+        boat: boat.name.clone(),
+        drummer: boat.drummer.map(|id| builder.seat(id, false)),
+        rows: grid
+            .iter()
+            .enumerate()
+            .map(|(i, row)| LineupRow {
+                row: i + 1,
+                left: row[0].map(|placed| builder.seat(placed.user_id, placed.pinned)),
+                right: row[1].map(|placed| builder.seat(placed.user_id, placed.pinned)),
+            })
+            .collect(),
+        steerer: boat.steerer.map(|id| builder.seat(id, false)),
+        alternates: alternates
+            .into_iter()
+            .map(|id| builder.seat(id, false))
+            .collect(),
+        left_right_kg: builder.left_right_kg(&grid),
+        front_back_moment: builder.moment(&grid, boat),
+    }
+}
+
+struct Builder<'a> {
+    users: &'a HashMap<ObjectId, User>,
+    flex: &'a [ObjectId],
+    default_weight: f32,
+}
+
+impl Builder<'_> {
+    fn weight(&self, user_id: &ObjectId) -> f32 {
+        self.users
+            .get(user_id)
+            .and_then(|user| user.weight_kg)
+            .unwrap_or(self.default_weight)
+    }
+
+    fn experience(&self, user_id: &ObjectId) -> Option<Experience> {
+        self.users.get(user_id).and_then(|user| user.experience)
+    }
+
+    fn seat_weight(&self, seat: &Option<Placed>) -> f32 {
+        seat.map_or(0.0, |placed| self.weight(&placed.user_id))
+    }
+
+    fn seat(&self, user_id: ObjectId, pinned: bool) -> Seat {
+        let user = self.users.get(&user_id);
+
+        Seat {
+            user_id,
+            name: user
+                .map(User::name)
+                .unwrap_or_else(|| "Unknown paddler".to_string()),
+            weight_kg: user.and_then(|user| user.weight_kg),
+            experience: user.and_then(|user| user.experience),
+            pinned,
+        }
+    }
+
+    fn moment(&self, grid: &Grid, boat: &Boat) -> f32 {
+        let rows = grid
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let offset = (i + 1) as f32 - CENTER_ROW;
+                (self.seat_weight(&row[0]) + self.seat_weight(&row[1])) * offset
+            })
+            .sum::<f32>();
+
+        let drummer = boat
+            .drummer
+            .map_or(0.0, |id| self.weight(&id) * (DRUMMER_ROW - CENTER_ROW));
+        let steerer = boat
+            .steerer
+            .map_or(0.0, |id| self.weight(&id) * (STEERER_ROW - CENTER_ROW));
+
+        rows + drummer + steerer
+    }
+
+    fn left_right_kg(&self, grid: &Grid) -> f32 {
+        grid.iter()
+            .map(|row| self.seat_weight(&row[0]) - self.seat_weight(&row[1]))
+            .sum()
+    }
+
+    /// Swaps paddlers of the same experience on the same side between rows,
+    /// taking the best swap each time until none reduces the moment.
+    fn balance_front_back(&self, grid: &mut Grid, boat: &Boat) {
+        let mut moment = self.moment(grid, boat);
+
+        loop {
+            let mut best: Option<(usize, usize, usize, f32)> = None;
+
+            for side in [0, 1] {
+                for a in 0..ROWS {
+                    for b in (a + 1)..ROWS {
+                        let (Some(first), Some(second)) = (grid[a][side], grid[b][side]) else {
+                            continue;
+                        };
+                        if first.pinned
+                            || second.pinned
+                            || self.experience(&first.user_id) != self.experience(&second.user_id)
+                        {
+                            continue;
+                        }
+
+                        let shift = (self.weight(&first.user_id) - self.weight(&second.user_id))
+                            * (b - a) as f32;
+                        let candidate = moment + shift;
+
+                        if candidate.abs() + EPSILON < best.map_or(moment.abs(), |(.., m)| m.abs()) {
+                            best = Some((side, a, b, candidate));
+                        }
+                    }
+                }
+            }
+
+            match best {
+                Some((side, a, b, candidate)) => {
+                    let first = grid[a][side];
+                    grid[a][side] = grid[b][side];
+                    grid[b][side] = first;
+                    moment = candidate;
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Swaps flex paddlers across the boat within a row, taking the best swap
+    /// each time until none evens out the sides further.
+    fn balance_sides(&self, grid: &mut Grid) {
+        let mut diff = self.left_right_kg(grid);
+
+        loop {
+            let mut best: Option<(usize, f32)> = None;
+
+            for (i, row) in grid.iter().enumerate() {
+                let movable = row.iter().flatten().all(|placed| {
+                    !placed.pinned && self.flex.contains(&placed.user_id)
+                });
+                if !movable || row.iter().all(|seat| seat.is_none()) {
+                    continue;
+                }
+
+                let candidate =
+                    diff - 2.0 * (self.seat_weight(&row[0]) - self.seat_weight(&row[1]));
+
+                if candidate.abs() + EPSILON < best.map_or(diff.abs(), |(_, d)| d.abs()) {
+                    best = Some((i, candidate));
+                }
+            }
+
+            match best {
+                Some((i, candidate)) => {
+                    grid[i].swap(0, 1);
+                    diff = candidate;
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{
+        practice::PracticeType,
+        user::{Gender, UserType},
+    };
+    use chrono::{Duration, Utc};
+
+    fn paddler(weight_kg: f32, experience: Experience) -> User {
+        User {
+            id: Some(ObjectId::new()),
+            first_name: "Test".to_string(),
+            last_name: "Paddler".to_string(),
+            gender: Gender::NA,
+            discord_id: None,
+            mcgill_id: String::new(),
+            email: String::new(),
+            user_type: UserType::Regular,
+            side: Side::NA,
+            qualifications: Vec::new(),
+            weight_kg: Some(weight_kg),
+            experience: Some(experience),
+            competitive_roster: false,
+            penalty_waived_until: None,
+            membership: Default::default(),
+            waiver: Default::default(),
+            display_name: None,
+            notifications: Default::default(),
+        }
+    }
+
+    fn practice_with(left: &[&User], right: &[&User]) -> Practice {
+        let start = Utc::now() + Duration::days(1);
+        let mut practice = Practice::new(
+            PracticeType::Water,
+            start,
+            start,
+            start + Duration::hours(2),
+            &PracticeType::Water.default_signup_window(),
+        )
+        .unwrap();
+
+        for (seat, user) in left.iter().enumerate() {
+            practice.boats[0].left_side[seat] = user.id;
+        }
+        for (seat, user) in right.iter().enumerate() {
+            practice.boats[0].right_side[seat] = user.id;
+        }
+        practice
+    }
+
+    fn by_id(users: impl IntoIterator<Item = User>) -> HashMap<ObjectId, User> {
+        users
+            .into_iter()
+            .map(|user| (user.id.unwrap(), user))
+            .collect()
+    }
+
+    fn left_at(lineup: &Lineup, row: usize) -> Option<ObjectId> {
+        lineup.rows[row - 1].left.as_ref().map(|seat| seat.user_id)
+    }
+
+    #[test]
+    fn most_experienced_paddler_takes_the_stroke_seat() {
+        let experienced = paddler(80.0, Experience::Experienced);
+        let novices = (0..9)
+            .map(|_| paddler(70.0, Experience::Novice))
+            .collect::<Vec<_>>();
+        let mut everyone = novices.iter().collect::<Vec<_>>();
+        everyone.push(&experienced);
+
+        let practice = practice_with(&everyone, &[]);
+        let experienced_id = experienced.id;
+        let lineup = build_lineup(
+            &practice,
+            0,
+            &by_id(novices.into_iter().chain([experienced])),
+        );
+
+        assert_eq!(left_at(&lineup, 1), experienced_id);
+        assert!(lineup.rows.iter().all(|row| row.left.is_some()));
+        assert!(lineup.alternates.is_empty());
+    }
+
+    #[test]
+    fn pinned_paddlers_keep_their_row() {
+        let experienced = paddler(80.0, Experience::Experienced);
+        let novice = paddler(70.0, Experience::Novice);
+        let (experienced_id, novice_id) = (experienced.id, novice.id);
+
+        let mut practice = practice_with(&[&experienced, &novice], &[]);
+        practice.lineup_overrides.push(LineupOverride {
+            user_id: novice_id.unwrap(),
+            boat: 0,
+            row: 1,
+            side: Side::Left,
+        });
+        let lineup = build_lineup(&practice, 0, &by_id([experienced, novice]));
+
+        assert_eq!(left_at(&lineup, 1), novice_id);
+        assert!(lineup.rows[0].left.as_ref().unwrap().pinned);
+        assert_eq!(left_at(&lineup, ROW_PRIORITY[1]), experienced_id);
+    }
+
+    #[test]
+    fn paddlers_past_the_last_row_are_alternates() {
+        let paddlers = (0..ROWS + 2)
+            .map(|_| paddler(70.0, Experience::Intermediate))
+            .collect::<Vec<_>>();
+        let practice = practice_with(&paddlers.iter().collect::<Vec<_>>(), &[]);
+        let lineup = build_lineup(&practice, 0, &by_id(paddlers));
+
+        assert_eq!(lineup.alternates.len(), 2);
+        assert!(lineup.rows.iter().all(|row| row.left.is_some()));
+    }
+
+    #[test]
+    fn balance_front_back_swaps_paddlers_of_the_same_level() {
+        let heavy = paddler(100.0, Experience::Novice);
+        let light = paddler(50.0, Experience::Novice);
+        let (heavy_id, light_id) = (heavy.id, light.id);
+
+        let practice = practice_with(&[&heavy, &light], &[]);
+        let lineup = build_lineup(&practice, 0, &by_id([heavy, light]));
+
+        // Heaviest first would put 100kg in row 1, but the swap is closer to even
+        assert_eq!(left_at(&lineup, 1), light_id);
+        assert_eq!(left_at(&lineup, 2), heavy_id);
+        assert!((lineup.front_back_moment - -575.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn balance_front_back_keeps_levels_in_place() {
+        let experienced = paddler(50.0, Experience::Experienced);
+        let novice = paddler(100.0, Experience::Novice);
+        let (experienced_id, novice_id) = (experienced.id, novice.id);
+
+        let practice = practice_with(&[&experienced, &novice], &[]);
+        let lineup = build_lineup(&practice, 0, &by_id([experienced, novice]));
+
+        assert_eq!(left_at(&lineup, 1), experienced_id);
+        assert_eq!(left_at(&lineup, 2), novice_id);
+    }
+
+    #[test]
+    fn balance_sides_only_moves_flex_paddlers() {
+        let heavy_flex = paddler(100.0, Experience::Experienced);
+        let light_flex = paddler(60.0, Experience::Experienced);
+        let fixed = paddler(90.0, Experience::Novice);
+        let (heavy_id, light_id, fixed_id) = (heavy_flex.id, light_flex.id, fixed.id);
+
+        let mut practice = practice_with(&[&heavy_flex, &fixed], &[&light_flex]);
+        practice.flex_paddlers = vec![heavy_id.unwrap(), light_id.unwrap()];
+        let lineup = build_lineup(&practice, 0, &by_id([heavy_flex, light_flex, fixed]));
+
+        assert_eq!(left_at(&lineup, 1), light_id);
+        assert_eq!(
+            lineup.rows[0].right.as_ref().map(|seat| seat.user_id),
+            heavy_id
+        );
+        assert_eq!(left_at(&lineup, 2), fixed_id);
+        assert!((lineup.left_right_kg - 50.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn members_only_see_their_own_weight() {
+        let viewer = paddler(80.0, Experience::Experienced);
+        let other = paddler(60.0, Experience::Novice);
+        let (viewer_id, other_id) = (viewer.id, other.id);
+
+        let practice = practice_with(&[&viewer], &[&other]);
+        let mut lineup = build_lineup(&practice, 0, &by_id([viewer, other]));
+        let balance = lineup.left_right_kg;
+        lineup.hide_weights(viewer_id);
+
+        let seats = lineup
+            .rows
+            .iter()
+            .flat_map(|row| [&row.left, &row.right])
+            .flatten()
+            .collect::<Vec<_>>();
+        let weight_of = |id| seats.iter().find(|seat| Some(seat.user_id) == id).unwrap().weight_kg;
+        assert_eq!(weight_of(viewer_id), Some(80.0));
+        assert_eq!(weight_of(other_id), None);
+        assert_eq!(lineup.left_right_kg, balance);
+    }
+}
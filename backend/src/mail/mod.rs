@@ -0,0 +1,28 @@
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use std::error::Error;
+
+/// Sends a plain text email from the club's account.
+pub async fn send_email(to: &str, subject: &str, body: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let host = std::env::var("SMTP_HOST").map_err(|_| "SMTP_HOST is not set")?;
+    let username = std::env::var("SMTP_USERNAME").map_err(|_| "SMTP_USERNAME is not set")?;
+    let password = std::env::var("SMTP_PASSWORD").map_err(|_| "SMTP_PASSWORD is not set")?;
+    let from: Mailbox = std::env::var("MAIL_FROM")
+        .unwrap_or_else(|_| username.clone())
+        .parse()?;
+
+    let message = Message::builder()
+        .from(from)
+        .to(to.parse()?)
+        .subject(subject)
+        .body(body.to_string())?;
+
+    let mailer = AsyncSmtpTransport::<Tokio1Executor>::relay(&host)?
+        .credentials(Credentials::new(username, password))
+        .build();
+    mailer.send(message).await?;
+
+    Ok(())
+}
@@ -3,20 +3,21 @@ mod logging;
 mod router;
 mod sheets;
 mod jobs;
+mod lineup;
+mod mail;
+mod notifications;
 
-use db::practice::Practice;
 use jobs::scheduler::SchedulerManager;
-use router::responses::PracticeStartInfo;
 use dotenv::dotenv;
 use sheets::sheets::SheetsClient;
-use std::error::Error;
 use std::sync::Arc;
 use tokio_cron_scheduler::{Job, JobScheduler};
-use tracing::info;
-use reqwest::Client as HttpClient;
+use tracing::{error, info};
 
 use crate::db::db::DB;
 use crate::router::router::create_router;
+use crate::router::seat_offers::recover_seat_offers;
+use crate::router::state::AppState;
 use crate::sheets::sheets::fetch_and_add_users;
 
 #[tokio::main]
@@ -24,14 +25,26 @@ async fn main() {
     dotenv().ok();
     logging::init_logging();
 
+    if std::env::var("API_TOKEN").map_or(true, |token| token.trim().is_empty()) {
+        panic!("API_TOKEN must be set");
+    }
+
     let db = Arc::new(DB::init().await.expect("Failed to initialize database"));
 
+    if let Err(e) = recover_seat_offers(&db).await {
+        error!("Failed to recover interrupted seat offers: {}", e);
+    }
+
     let form_client = Arc::new(
         SheetsClient::init_form_client(db.clone())
             .await
             .expect("Failed to initialize form sheets client"),
     );
 
+    if let Err(e) = form_client.backfill_form_answers().await {
+        error!("Failed to backfill form answers: {}", e);
+    }
+
     let practice_client = Arc::new(
       SheetsClient::init_practice_client(db.clone())
         .await
@@ -47,7 +60,11 @@ async fn main() {
       .await
       .expect("Failed to schedule jobs");
 
-    let app = create_router(db.clone());
+    let app = create_router(AppState {
+        db: db.clone(),
+        scheduler: scheduler_manager.clone(),
+        practice_client: practice_client.clone(),
+    });
 
     let scheduler = JobScheduler::new().await.unwrap();
     let db_clone = db.clone();
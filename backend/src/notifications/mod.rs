@@ -0,0 +1,62 @@
+use mongodb::bson::oid::ObjectId;
+use reqwest::Client as HttpClient;
+use std::error::Error;
+use tracing::{error, info};
+
+use crate::db::{db::DB, user::User};
+use crate::router::responses::DirectMessageNotification;
+
+const DISCORD_BOT_URL: &str = "http://discord-bot:3001";
+
+pub async fn send_dm(discord_id: &str, message: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let notification = DirectMessageNotification {
+        discord_id: discord_id.to_string(),
+        message: message.to_string(),
+    };
+
+    let response = HttpClient::new()
+        .post(format!("{}/dm", DISCORD_BOT_URL))
+        .json(&notification)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(format!("Failed to send DM to {}: {}", discord_id, response.status()).into());
+    }
+
+    Ok(())
+}
+
+/// DMs every given user that has linked a Discord account and not turned
+/// DMs off. Failures are logged and skipped so one unreachable member doesn't
+/// stop the rest from being told.
+pub async fn notify_users(db: &DB, user_ids: impl IntoIterator<Item = ObjectId>, message: &str) {
+    for user_id in user_ids {
+        match db.get_user(user_id).await {
+            Ok(Some(user)) if user.notifications.direct_messages => dm_user(&user, message).await,
+            Ok(_) => {}
+            Err(e) => error!("Failed to look up user {}: {}", user_id, e),
+        }
+    }
+}
+
+/// DMs every exec that still wants exec alerts.
+pub async fn notify_execs(db: &DB, message: &str) {
+    match db.get_execs().await {
+        Ok(execs) => {
+            for exec in execs.iter().filter(|exec| exec.notifications.exec_alerts) {
+                dm_user(exec, message).await;
+            }
+        }
+        Err(e) => error!("Failed to look up execs: {}", e),
+    }
+}
+
+async fn dm_user(user: &User, message: &str) {
+    if let Some(discord_id) = &user.discord_id {
+        info!("Notifying user {:?} ({})", user.id, discord_id);
+        if let Err(e) = send_dm(discord_id, message).await {
+            error!("{}", e);
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::Utc;
+use mongodb::bson::oid::ObjectId;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::db::{
+    attendance::{Attendance, AttendanceStatus},
+    db::DB,
+    practice::Practice,
+    user::Permission,
+};
+
+use super::{
+    auth::Actor,
+    requests::{CheckInRequest, RecordAttendanceRequest},
+};
+
+/// Checks in several members at once, e.g. the whole boat from the dock.
+pub(crate) async fn record_attendance(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(practice_id): Path<String>,
+    Json(req): Json<RecordAttendanceRequest>,
+) -> Result<Json<Vec<Attendance>>, String> {
+    actor.require(Permission::EditRosters)?;
+
+    let practice = checkin_practice(&db, &practice_id).await?;
+
+    // Validate everything first so a bad entry doesn't leave half a check-in
+    let entries = req
+        .records
+        .into_iter()
+        .map(|entry| {
+            let user_id = ObjectId::parse_str(&entry.user_id).map_err(|e| e.to_string())?;
+            check_seated(&practice, user_id)?;
+            Ok((user_id, entry.status))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    for (user_id, status) in entries {
+        save_attendance(&db, &practice, user_id, status).await?;
+    }
+
+    practice_attendance(&db, &practice).await.map(Json)
+}
+
+pub(crate) async fn check_in(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path((practice_id, user_id)): Path<(String, String)>,
+    Json(req): Json<CheckInRequest>,
+) -> Result<Json<Attendance>, String> {
+    actor.require(Permission::EditRosters)?;
+
+    let practice = checkin_practice(&db, &practice_id).await?;
+    let user_id = ObjectId::parse_str(&user_id).map_err(|e| e.to_string())?;
+    check_seated(&practice, user_id)?;
+
+    save_attendance(&db, &practice, user_id, req.status)
+        .await
+        .map(Json)
+}
+
+pub(crate) async fn get_practice_attendance(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(practice_id): Path<String>,
+) -> Result<Json<Vec<Attendance>>, String> {
+    actor.require(Permission::EditRosters)?;
+    let practice_id = ObjectId::parse_str(&practice_id).map_err(|e| e.to_string())?;
+
+    let practice = db
+        .get_practice(practice_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Practice not found")?;
+
+    practice_attendance(&db, &practice).await.map(Json)
+}
+
+pub(crate) async fn get_user_attendance(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(user_id): Path<String>,
+) -> Result<Json<Vec<Attendance>>, String> {
+    let user_id = ObjectId::parse_str(&user_id).map_err(|e| e.to_string())?;
+    actor.require_user_or(user_id, Permission::EditRosters)?;
+
+    let records = db
+        .get_user_attendance(user_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Json(records))
+}
+
+/// Loads a practice that attendance can be taken for: not cancelled, and
+/// with its roster final.
+async fn checkin_practice(db: &DB, practice_id: &str) -> Result<Practice, String> {
+    let practice_id = ObjectId::parse_str(practice_id).map_err(|e| e.to_string())?;
+
+    let practice = db
+        .get_practice(practice_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Practice not found")?;
+
+    if practice.is_cancelled() {
+        return Err("Practice has been cancelled".to_string());
+    }
+    if Utc::now() < practice.signup_closes_at {
+        return Err("Attendance can be taken once signups close".to_string());
+    }
+
+    Ok(practice)
+}
+
+/// Only members with a seat, role seats included, can be at a practice.
+/// Waitlisted members and lottery entrants never made it into the boat.
+fn check_seated(practice: &Practice, user_id: ObjectId) -> Result<(), String> {
+    if practice.seat_ref(user_id).is_none() {
+        return Err(format!("User {} has no seat on this practice", user_id));
+    }
+    Ok(())
+}
+
+async fn save_attendance(
+    db: &DB,
+    practice: &Practice,
+    user_id: ObjectId,
+    status: AttendanceStatus,
+) -> Result<Attendance, String> {
+    let practice_id = practice.id.ok_or("Practice has no ID")?;
+
+    let attendance = Attendance {
+        id: None,
+        practice_id,
+        user_id,
+        practice_start: practice.start_time,
+        status,
+        recorded_at: Utc::now(),
+    };
+
+    info!(
+        "Recording {:?} for user {} at practice {}",
+        status, user_id, practice_id
+    );
+    db.record_attendance(&attendance)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(attendance)
+}
+
+async fn practice_attendance(db: &DB, practice: &Practice) -> Result<Vec<Attendance>, String> {
+    db.get_practice_attendance(practice.id.ok_or("Practice has no ID")?)
+        .await
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,41 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use mongodb::bson::oid::ObjectId;
+use std::sync::Arc;
+
+use crate::db::{audit::AuditEntry, db::DB, user::Permission};
+
+use super::auth::Actor;
+
+/// Every roster change on a practice, oldest first.
+pub(crate) async fn get_practice_audit(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(practice_id): Path<String>,
+) -> Result<Json<Vec<AuditEntry>>, String> {
+    actor.require(Permission::EditRosters)?;
+    let practice_id = ObjectId::parse_str(&practice_id).map_err(|e| e.to_string())?;
+
+    db.get_practice_audit(practice_id)
+        .await
+        .map(Json)
+        .map_err(|e| e.to_string())
+}
+
+/// Every roster change involving a member, oldest first. Members can look up
+/// their own history.
+pub(crate) async fn get_user_audit(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(user_id): Path<String>,
+) -> Result<Json<Vec<AuditEntry>>, String> {
+    let user_id = ObjectId::parse_str(&user_id).map_err(|e| e.to_string())?;
+    actor.require_user_or(user_id, Permission::EditRosters)?;
+
+    db.get_user_audit(user_id)
+        .await
+        .map(Json)
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,170 @@
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
+};
+use mongodb::bson::oid::ObjectId;
+use std::sync::Arc;
+
+use crate::db::{
+    audit::{AuditAction, AuditSource, RosterChange},
+    db::DB,
+    user::{Permission, User, UserType},
+};
+
+/// Header the bot sets to the Discord account of the member it acts for.
+const DISCORD_ID_HEADER: &str = "X-Discord-Id";
+
+/// Header the bot sets so audit entries can tell its requests apart.
+const CLIENT_HEADER: &str = "X-Client";
+
+/// Comma-separated Discord accounts that always act as admins, so a fresh
+/// deployment has someone who can hand out the other roles.
+const ADMIN_DISCORD_IDS: &str = "ADMIN_DISCORD_IDS";
+
+/// A request made with the shared API token, i.e. by the bot or another
+/// trusted client.
+pub(crate) struct ApiClient;
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ApiClient
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        // docker-compose passes an unset variable through as an empty one,
+        // which must never match an empty bearer token
+        let token = std::env::var("API_TOKEN")
+            .ok()
+            .filter(|token| !token.trim().is_empty())
+            .ok_or((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "API_TOKEN is not set".to_string(),
+            ))?;
+
+        let provided = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        if provided != Some(token.as_str()) {
+            return Err((StatusCode::UNAUTHORIZED, "Invalid API token".to_string()));
+        }
+
+        Ok(ApiClient)
+    }
+}
+
+/// The member making an authenticated request.
+pub(crate) struct Actor {
+    /// The member as stored, safe to write back.
+    pub user: User,
+    /// What the member may do, which is Admin for bootstrap admins whatever
+    /// their stored type.
+    pub user_type: UserType,
+    pub source: AuditSource,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Actor
+where
+    Arc<DB>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        ApiClient::from_request_parts(parts, state).await?;
+
+        let discord_id = parts
+            .headers
+            .get(DISCORD_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .ok_or((
+                StatusCode::UNAUTHORIZED,
+                format!("Missing {} header", DISCORD_ID_HEADER),
+            ))?
+            .to_string();
+
+        let db = Arc::<DB>::from_ref(state);
+        let user = db
+            .get_user_by_discord_id(&discord_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .ok_or((
+                StatusCode::FORBIDDEN,
+                "Your Discord account isn't linked to a member".to_string(),
+            ))?;
+
+        let user_type = if is_bootstrap_admin(&discord_id) {
+            UserType::Admin
+        } else {
+            user.user_type
+        };
+
+        let source = match parts.headers.get(CLIENT_HEADER).and_then(|value| value.to_str().ok()) {
+            Some("discord-bot") => AuditSource::Bot,
+            _ => AuditSource::Api,
+        };
+
+        Ok(Actor {
+            user,
+            user_type,
+            source,
+        })
+    }
+}
+
+fn is_bootstrap_admin(discord_id: &str) -> bool {
+    std::env::var(ADMIN_DISCORD_IDS)
+        .map(|ids| ids.split(',').any(|id| id.trim() == discord_id))
+        .unwrap_or(false)
+}
+
+impl Actor {
+    pub fn require(&self, permission: Permission) -> Result<(), String> {
+        if self.user_type.has_permission(permission) {
+            Ok(())
+        } else {
+            Err(format!(
+                "{:?} members don't have the {:?} permission",
+                self.user_type, permission
+            ))
+        }
+    }
+
+    /// A roster change made by this member.
+    pub fn change(&self, action: AuditAction) -> RosterChange {
+        RosterChange {
+            actor: self.user.id,
+            subject: None,
+            source: self.source,
+            action,
+        }
+    }
+
+    pub fn is(&self, discord_id: &str) -> bool {
+        self.user.discord_id.as_deref() == Some(discord_id)
+    }
+
+    /// Like `require_self_or`, for routes that name the member by their ID.
+    pub fn require_user_or(&self, user_id: ObjectId, permission: Permission) -> Result<(), String> {
+        if self.user.id == Some(user_id) {
+            Ok(())
+        } else {
+            self.require(permission)
+        }
+    }
+
+    /// Members can act for themselves; acting for anyone else takes `permission`.
+    pub fn require_self_or(&self, discord_id: &str, permission: Permission) -> Result<(), String> {
+        if self.is(discord_id) {
+            Ok(())
+        } else {
+            self.require(permission)
+        }
+    }
+}
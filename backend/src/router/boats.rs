@@ -0,0 +1,90 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono_tz::America::New_York;
+use mongodb::bson::oid::ObjectId;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::{
+    db::{
+        audit::AuditAction,
+        db::DB,
+        practice::{Boat, Practice},
+        user::Permission,
+    },
+    notifications::notify_users,
+};
+
+use super::{auth::Actor, requests::AddBoatRequest, roster::edit_roster};
+
+/// The most seats a boat can have on one side. Anything bigger is a typo, and
+/// every seat is stored with the practice.
+const MAX_SEATS_PER_SIDE: usize = 20;
+
+/// Builds a boat for a practice, sized like the practice type's default boat
+/// unless the request says otherwise.
+pub(crate) fn boat_from_request(practice: &Practice, req: AddBoatRequest) -> Result<Boat, String> {
+    if !practice.practice_type.has_sides() {
+        return Err(format!("{:?} practices have no boats", practice.practice_type));
+    }
+
+    let (seats, _) = practice.practice_type.default_capacity();
+    let seats = req.seats_per_side.unwrap_or(seats);
+    if !(1..=MAX_SEATS_PER_SIDE).contains(&seats) {
+        return Err(format!(
+            "A boat needs between 1 and {} seats per side",
+            MAX_SEATS_PER_SIDE
+        ));
+    }
+
+    let mut boat = Boat::new(
+        req.name
+            .unwrap_or_else(|| format!("Boat {}", practice.boats.len() + 1)),
+        seats,
+        true,
+    );
+    boat.skill_group = req.skill_group;
+
+    Ok(boat)
+}
+
+/// Puts another boat on the water. Waitlisted members are seated in it right
+/// away and told they made the main list.
+pub(crate) async fn add_boat(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(practice_id): Path<String>,
+    Json(req): Json<AddBoatRequest>,
+) -> Result<Json<Practice>, String> {
+    actor.require(Permission::SchedulePractices)?;
+
+    let practice_id = ObjectId::parse_str(&practice_id).map_err(|e| e.to_string())?;
+
+    let change = actor.change(AuditAction::Promote);
+    let (practice, promoted) = edit_roster(&db, practice_id, &change, |practice| {
+        if practice.is_cancelled() {
+            return Err("Practice has been cancelled".to_string());
+        }
+        if !practice.is_future() {
+            return Err("Practice has already started".to_string());
+        }
+
+        let boat = boat_from_request(practice, req.clone())?;
+        info!("Adding {} to practice {}", boat.name, practice_id);
+        Ok(practice.add_boat(boat))
+    })
+    .await??;
+
+    let message = format!(
+        "Another boat was added to the practice on {}, you're now on the main list!",
+        practice
+            .start_time
+            .with_timezone(&New_York)
+            .format("%A, %B %d at %I:%M %p")
+    );
+    notify_users(&db, promoted, &message).await;
+
+    Ok(Json(practice))
+}
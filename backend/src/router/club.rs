@@ -0,0 +1,31 @@
+use axum::{extract::State, Json};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::db::{club::ClubSettings, db::DB, user::Permission};
+
+use super::auth::{Actor, ApiClient};
+
+pub(crate) async fn get_club_settings(
+    _client: ApiClient,
+    State(db): State<Arc<DB>>,
+) -> Result<Json<ClubSettings>, String> {
+    let settings = db.get_club_settings().await.map_err(|e| e.to_string())?;
+    Ok(Json(settings))
+}
+
+pub(crate) async fn update_club_settings(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Json(settings): Json<ClubSettings>,
+) -> Result<Json<ClubSettings>, String> {
+    actor.require(Permission::ConfigureClub)?;
+
+    settings.validate()?;
+
+    info!("Updating club settings");
+    db.update_club_settings(&settings)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(Json(settings))
+}
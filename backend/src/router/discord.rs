@@ -0,0 +1,227 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{Duration, Utc};
+use mongodb::bson::oid::ObjectId;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use crate::{
+    db::{
+        db::DB,
+        discord_relink::{DiscordRelink, CODE_VALID_MINUTES, MAX_ATTEMPTS},
+        user::{Permission, User},
+    },
+    mail::send_email,
+    notifications::send_dm,
+};
+
+use super::{
+    auth::{Actor, ApiClient},
+    requests::{LinkDiscordRequest, RelinkDiscordRequest, VerifyRelinkRequest},
+};
+
+/// Members have to wait this long before asking for another code.
+const RELINK_COOLDOWN_SECONDS: i64 = 60;
+
+async fn user_by_email(db: &DB, email: &str) -> Result<(User, ObjectId), String> {
+    let user = db
+        .get_user_by_email(email.trim())
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("User not found with given email")?;
+    let user_id = user.id.ok_or("User has no ID")?;
+
+    Ok((user, user_id))
+}
+
+/// Lets the member know their account moved, in case it wasn't them.
+async fn notify_old_account(old_discord_id: Option<String>, new_discord_id: Option<&str>) {
+    let Some(old_discord_id) = old_discord_id.filter(|old| Some(old.as_str()) != new_discord_id) else {
+        return;
+    };
+
+    let message = "Your club account is no longer linked to this Discord account. \
+        If you didn't ask for this, contact an exec.";
+    if let Err(e) = send_dm(&old_discord_id, message).await {
+        error!("{}", e);
+    }
+}
+
+/// Starts moving a member's account to the Discord account making the
+/// request. A code is emailed to them to prove the account is theirs.
+pub(crate) async fn request_relink(
+    _client: ApiClient,
+    State(db): State<Arc<DB>>,
+    Json(req): Json<RelinkDiscordRequest>,
+) -> Result<Json<String>, String> {
+    let (user, user_id) = user_by_email(&db, &req.email).await?;
+
+    if user.discord_id.as_deref() == Some(req.discord_id.as_str()) {
+        return Err("This Discord account is already linked to you".to_string());
+    }
+    let linked = db
+        .get_user_by_discord_id(&req.discord_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    if linked.is_some() {
+        return Err("This Discord account is linked to another member, unlink it first".to_string());
+    }
+
+    let pending = db
+        .get_discord_relink(user_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    if pending.is_some_and(|pending| {
+        Utc::now() < pending.created_at + Duration::seconds(RELINK_COOLDOWN_SECONDS)
+    }) {
+        return Err("A code was just sent, check your email".to_string());
+    }
+
+    let relink = DiscordRelink::new(user_id, req.discord_id);
+    db.save_discord_relink(&relink)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    send_email(
+        &user.email,
+        "Confirm your new Discord account",
+        &format!(
+            "Someone asked to link your club account to a new Discord account.\n\n\
+            Your code is {}. It expires in {} minutes.\n\n\
+            If this wasn't you, you can ignore this email.",
+            relink.code, CODE_VALID_MINUTES
+        ),
+    )
+    .await
+    .map_err(|e| format!("Failed to send the code: {}", e))?;
+    info!("Sent a Discord relink code to user {}", user_id);
+
+    Ok(Json(format!(
+        "We emailed a code to {}, send it back to finish linking this account",
+        user.email
+    )))
+}
+
+/// Finishes a relink with the emailed code, unlinking the member's old
+/// Discord account.
+pub(crate) async fn verify_relink(
+    _client: ApiClient,
+    State(db): State<Arc<DB>>,
+    Json(req): Json<VerifyRelinkRequest>,
+) -> Result<Json<String>, String> {
+    let (user, user_id) = user_by_email(&db, &req.email).await?;
+
+    let mut relink = db
+        .get_discord_relink(user_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .filter(|relink| relink.discord_id == req.discord_id)
+        .ok_or("No relink was started from this Discord account")?;
+
+    if relink.is_expired() {
+        db.delete_discord_relink(user_id)
+            .await
+            .map_err(|e| e.to_string())?;
+        return Err("This code has expired, ask for a new one".to_string());
+    }
+
+    if relink.code != req.code.trim() {
+        relink.attempts += 1;
+        if relink.attempts >= MAX_ATTEMPTS {
+            db.delete_discord_relink(user_id)
+                .await
+                .map_err(|e| e.to_string())?;
+            return Err("Too many wrong codes, ask for a new one".to_string());
+        }
+        db.save_discord_relink(&relink)
+            .await
+            .map_err(|e| e.to_string())?;
+        return Err("Wrong code".to_string());
+    }
+
+    db.set_discord_id(user_id, Some(&relink.discord_id))
+        .await
+        .map_err(|e| e.to_string())?;
+    db.delete_discord_relink(user_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    info!(
+        "User {} moved from Discord account {:?} to {}",
+        user_id, user.discord_id, relink.discord_id
+    );
+    notify_old_account(user.discord_id, Some(&relink.discord_id)).await;
+
+    Ok(Json("Linked this Discord account to your club account".to_string()))
+}
+
+/// Unlinks the Discord account making the request.
+pub(crate) async fn unlink_self(actor: Actor, State(db): State<Arc<DB>>) -> Result<Json<String>, String> {
+    let user_id = actor.user.id.ok_or("User has no ID")?;
+
+    db.set_discord_id(user_id, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    info!("User {} unlinked their Discord account", user_id);
+
+    Ok(Json("Unlinked your Discord account".to_string()))
+}
+
+/// Links a member to a Discord account without a code, for members who lost
+/// access to their email too.
+pub(crate) async fn link_discord(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(user_id): Path<String>,
+    Json(req): Json<LinkDiscordRequest>,
+) -> Result<Json<User>, String> {
+    actor.require(Permission::ManageMembers)?;
+
+    let user_id = ObjectId::parse_str(&user_id).map_err(|e| e.to_string())?;
+    let mut user = db
+        .get_user(user_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("User not found")?;
+
+    db.set_discord_id(user_id, Some(&req.discord_id))
+        .await
+        .map_err(|e| e.to_string())?;
+    db.delete_discord_relink(user_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    info!(
+        "{:?} linked user {} to Discord account {}",
+        actor.user.id, user_id, req.discord_id
+    );
+
+    let old_discord_id = user.discord_id.replace(req.discord_id);
+    notify_old_account(old_discord_id, user.discord_id.as_deref()).await;
+
+    Ok(Json(user))
+}
+
+pub(crate) async fn unlink_discord(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(user_id): Path<String>,
+) -> Result<Json<User>, String> {
+    actor.require(Permission::ManageMembers)?;
+
+    let user_id = ObjectId::parse_str(&user_id).map_err(|e| e.to_string())?;
+    let mut user = db
+        .get_user(user_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("User not found")?;
+
+    db.set_discord_id(user_id, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    info!("{:?} unlinked the Discord account of user {}", actor.user.id, user_id);
+
+    notify_old_account(user.discord_id.take(), None).await;
+
+    Ok(Json(user))
+}
@@ -0,0 +1,292 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::Utc;
+use chrono_tz::America::New_York;
+use mongodb::bson::oid::ObjectId;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::{
+    db::{
+        audit::AuditAction,
+        db::DB,
+        guest::Guest,
+        practice::{Practice, PracticeError},
+        user::{Permission, Side, User},
+    },
+    notifications::notify_users,
+};
+
+use super::{
+    auth::Actor,
+    requests::{CreateGuestRequest, GuestSignupRequest, SetGuestWaiverRequest},
+    responses::SignupResponse,
+    roster::edit_roster,
+};
+
+/// Records a guest, or updates the record if they've been brought before.
+/// Only the member who first brought them or an exec can change an existing
+/// record.
+pub(crate) async fn create_guest(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Json(req): Json<CreateGuestRequest>,
+) -> Result<Json<Guest>, String> {
+    actor.require_self_or(&req.discord_id, Permission::EditRosters)?;
+
+    let host = member(&db, &req.discord_id).await?;
+    let email = req.email.trim().to_string();
+
+    if db
+        .get_user_by_email(&email)
+        .await
+        .map_err(|e| e.to_string())?
+        .is_some()
+    {
+        return Err("Someone with that email is already a member".to_string());
+    }
+
+    let existing = db
+        .get_guest_by_email(&email)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let guest = match existing {
+        // Someone else's guest: hand back the record's ID so they can be
+        // signed up, but nothing beyond what the requester already gave us
+        Some(guest)
+            if Some(guest.invited_by) != host.id
+                && actor.require(Permission::EditRosters).is_err() =>
+        {
+            Guest {
+                first_name: req.first_name,
+                last_name: req.last_name,
+                email,
+                side: req.side.unwrap_or(Side::NA),
+                ..guest
+            }
+        }
+        Some(mut guest) => {
+            guest.first_name = req.first_name;
+            guest.last_name = req.last_name;
+            guest.side = req.side.unwrap_or(guest.side);
+            guest.waiver_signed |= req.waiver_signed;
+
+            db.update_guest(&guest).await.map_err(|e| e.to_string())?;
+            guest
+        }
+        None => {
+            let mut guest = Guest {
+                id: None,
+                first_name: req.first_name,
+                last_name: req.last_name,
+                email,
+                side: req.side.unwrap_or(Side::NA),
+                waiver_signed: req.waiver_signed,
+                invited_by: host.id.ok_or("User has no ID")?,
+                created_at: Utc::now(),
+                converted_at: None,
+            };
+
+            let guest_id = db.create_guest(&guest).await.map_err(|e| e.to_string())?;
+            guest.id = Some(guest_id);
+            info!("Created guest {} invited by {:?}", guest_id, guest.invited_by);
+            guest
+        }
+    };
+
+    Ok(Json(guest))
+}
+
+/// Guests are visible to the member who invited them and to roster editors.
+pub(crate) async fn get_guest(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(guest_id): Path<String>,
+) -> Result<Json<Guest>, String> {
+    let guest_id = ObjectId::parse_str(&guest_id).map_err(|e| e.to_string())?;
+
+    let guest = db
+        .get_guest(guest_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Guest not found")?;
+    actor.require_user_or(guest.invited_by, Permission::EditRosters)?;
+
+    Ok(Json(guest))
+}
+
+pub(crate) async fn set_guest_waiver(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(guest_id): Path<String>,
+    Json(req): Json<SetGuestWaiverRequest>,
+) -> Result<Json<Guest>, String> {
+    actor.require(Permission::ManageMembers)?;
+
+    let guest_id = ObjectId::parse_str(&guest_id).map_err(|e| e.to_string())?;
+
+    let mut guest = db
+        .get_guest(guest_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Guest not found")?;
+
+    guest.waiver_signed = req.waiver_signed;
+    db.update_guest(&guest).await.map_err(|e| e.to_string())?;
+
+    info!("Set waiver of guest {} to {}", guest_id, req.waiver_signed);
+    Ok(Json(guest))
+}
+
+/// Signs a guest up as the requesting member's plus-one.
+pub(crate) async fn signup_guest(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Json(req): Json<GuestSignupRequest>,
+) -> Result<Json<SignupResponse>, String> {
+    actor.require_self_or(&req.discord_id, Permission::EditRosters)?;
+
+    let host = member(&db, &req.discord_id).await?;
+    let (practice, guest) = practice_and_guest(&db, &req).await?;
+    let practice_id = practice.id.ok_or("Practice has no ID")?;
+
+    if guest.is_converted() {
+        return Err("This guest has registered, they can sign up themselves".to_string());
+    }
+    if !guest.waiver_signed {
+        return Err("Guests need a signed waiver before they can paddle".to_string());
+    }
+
+    let settings = db
+        .get_club_settings()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let change = actor.change(AuditAction::Signup);
+    match edit_roster(&db, practice_id, &change, |practice| {
+        practice.add_guest(&guest, &host, &settings.guests)
+    })
+    .await?
+    {
+        Ok((practice, seated)) => {
+            info!(
+                "Guest {:?} signed up for practice {:?} by {:?}",
+                guest.id, practice.id, host.id
+            );
+            let message = if seated {
+                format!("Signed up {} {} as your guest", guest.first_name, guest.last_name)
+            } else {
+                format!(
+                    "Practice is full, {} {} is on the waitlist as your guest",
+                    guest.first_name, guest.last_name
+                )
+            };
+
+            Ok(Json(SignupResponse {
+                success: true,
+                message,
+                on_waitlist: !seated,
+            }))
+        }
+        Err(
+            e @ (PracticeError::Locked
+            | PracticeError::Cancelled
+            | PracticeError::Full
+            | PracticeError::AlreadySignedUp
+            | PracticeError::GuestLimit
+            | PracticeError::HostGuestLimit
+            | PracticeError::LotteryOpen),
+        ) => Ok(Json(SignupResponse {
+            success: false,
+            message: e.to_string(),
+            on_waitlist: false,
+        })),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Takes a guest off a practice. Only the member who brought them or an exec
+/// can do this.
+pub(crate) async fn unregister_guest(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Json(req): Json<GuestSignupRequest>,
+) -> Result<Json<SignupResponse>, String> {
+    actor.require_self_or(&req.discord_id, Permission::EditRosters)?;
+
+    let (practice, guest) = practice_and_guest(&db, &req).await?;
+    let practice_id = practice.id.ok_or("Practice has no ID")?;
+    let guest_id = guest.id.ok_or("Guest has no ID")?;
+
+    let host_id = practice
+        .guests
+        .iter()
+        .find(|entry| entry.guest_id == guest_id)
+        .map(|entry| entry.host_id);
+    let Some(host_id) = host_id else {
+        return Ok(Json(SignupResponse {
+            success: false,
+            message: "Guest not registered for this practice".to_string(),
+            on_waitlist: false,
+        }));
+    };
+    let requester = member(&db, &req.discord_id).await?;
+    if requester.id != Some(host_id) && actor.require(Permission::EditRosters).is_err() {
+        return Err("Only the member who brought this guest can remove them".to_string());
+    }
+
+    let change = actor.change(AuditAction::Unregister);
+    let (practice, promoted) = edit_roster(&db, practice_id, &change, |practice| {
+        practice.remove_by_id(guest_id)
+    })
+    .await?
+    .map_err(|e| e.to_string())?;
+    info!("Guest {} removed from practice {:?}", guest_id, practice.id);
+
+    notify_users(
+        &db,
+        promoted,
+        &format!(
+            "A seat opened up, you're now on the main list for the practice on {}",
+            practice
+                .start_time
+                .with_timezone(&New_York)
+                .format("%A, %B %d at %I:%M %p")
+        ),
+    )
+    .await;
+
+    Ok(Json(SignupResponse {
+        success: true,
+        message: format!("Removed {} {} from the practice", guest.first_name, guest.last_name),
+        on_waitlist: false,
+    }))
+}
+
+async fn member(db: &DB, discord_id: &str) -> Result<User, String> {
+    db.get_user_by_discord_id(discord_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Only registered members can bring guests".to_string())
+}
+
+async fn practice_and_guest(db: &DB, req: &GuestSignupRequest) -> Result<(Practice, Guest), String> {
+    let practice_id = ObjectId::parse_str(&req.practice_id).map_err(|e| e.to_string())?;
+    let guest_id = ObjectId::parse_str(&req.guest_id).map_err(|e| e.to_string())?;
+
+    let practice = db
+        .get_practice(practice_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Practice not found")?;
+    let guest = db
+        .get_guest(guest_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Guest not found")?;
+
+    Ok((practice, guest))
+}
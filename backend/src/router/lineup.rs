@@ -0,0 +1,108 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use mongodb::bson::{doc, oid::ObjectId, to_bson};
+use std::{collections::HashMap, sync::Arc};
+use tracing::info;
+
+use crate::{
+    db::{
+        db::DB,
+        practice::{Boat, LineupOverride, Practice},
+        user::Permission,
+    },
+    lineup::{build_lineup, validate_overrides, Lineup},
+};
+
+use super::{auth::Actor, requests::SetLineupOverridesRequest};
+
+/// Members only see their own weight; roster editors see everyone's.
+pub(crate) async fn get_lineup(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(practice_id): Path<String>,
+) -> Result<Json<Vec<Lineup>>, String> {
+    let practice_id = ObjectId::parse_str(&practice_id).map_err(|e| e.to_string())?;
+
+    let practice = db
+        .get_practice(practice_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Practice not found")?;
+
+    let mut lineups = lineup_for(&db, &practice).await?;
+    if actor.require(Permission::EditRosters).is_err() {
+        for lineup in &mut lineups {
+            lineup.hide_weights(actor.user.id);
+        }
+    }
+
+    Ok(Json(lineups))
+}
+
+pub(crate) async fn set_lineup_overrides(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(practice_id): Path<String>,
+    Json(req): Json<SetLineupOverridesRequest>,
+) -> Result<Json<Vec<Lineup>>, String> {
+    actor.require(Permission::EditRosters)?;
+
+    let practice_id = ObjectId::parse_str(&practice_id).map_err(|e| e.to_string())?;
+
+    let mut practice = db
+        .get_practice(practice_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Practice not found")?;
+
+    let overrides = req
+        .overrides
+        .into_iter()
+        .map(|pinned| {
+            Ok(LineupOverride {
+                user_id: ObjectId::parse_str(&pinned.user_id).map_err(|e| e.to_string())?,
+                boat: pinned.boat,
+                row: pinned.row,
+                side: pinned.side,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    validate_overrides(&practice, &overrides)?;
+
+    info!("Pinning {} seats of practice {}", overrides.len(), practice_id);
+    db.update_practice_fields(
+        &mut practice,
+        doc! {"lineup_overrides": to_bson(&overrides).map_err(|e| e.to_string())?},
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    lineup_for(&db, &practice).await.map(Json)
+}
+
+/// Builds the lineup of every boat of a practice.
+async fn lineup_for(db: &DB, practice: &Practice) -> Result<Vec<Lineup>, String> {
+    if !practice.practice_type.has_boat_roles() {
+        return Err(format!("{:?} practices have no boat lineup", practice.practice_type));
+    }
+
+    let user_ids = practice
+        .boats
+        .iter()
+        .flat_map(Boat::seated)
+        .collect::<Vec<_>>();
+
+    let users = db
+        .get_users(&user_ids)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter_map(|user| Some((user.id?, user)))
+        .collect::<HashMap<_, _>>();
+
+    Ok((0..practice.boats.len())
+        .map(|boat| build_lineup(practice, boat, &users))
+        .collect())
+}
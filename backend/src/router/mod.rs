@@ -1,3 +1,17 @@
+#[allow(clippy::module_inception)]
 pub mod router;
 pub mod requests;
 pub mod responses;
+pub mod attendance;
+pub mod audit;
+pub mod auth;
+pub mod boats;
+pub mod club;
+pub mod discord;
+pub mod guests;
+pub mod lineup;
+pub mod roster;
+pub mod seat_offers;
+pub mod series;
+pub mod state;
+pub mod users;
@@ -1,6 +1,8 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc, Weekday};
 use serde::Deserialize;
 
+use crate::db::{attendance::AttendanceStatus, club::EarlyAccess, lottery::LotteryWeighting, practice::{BoatAssignment, ManualLock, Placement, PracticeType}, series::DateRange, user::{BoatRole, Experience, MembershipPlan, MembershipStatus, NotificationSettings, Side, UserType}};
+
 #[derive(Deserialize)]
 pub struct CreateDiscordUser {
   pub email: String,
@@ -9,12 +11,226 @@ pub struct CreateDiscordUser {
 
 #[derive(Deserialize)]
 pub struct CreatePracticeRequest {
+  #[serde(default)]
+  pub practice_type: PracticeType,
   pub date: DateTime<Utc>,
-  pub start_time : DateTime<Utc>
+  pub start_time : DateTime<Utc>,
+  pub end_time: Option<DateTime<Utc>>,
+  pub duration_minutes: Option<i64>,
+  pub signup_opens_at: Option<DateTime<Utc>>,
+  pub signup_closes_at: Option<DateTime<Utc>>,
+  pub waitlist_transfer_at: Option<DateTime<Utc>>,
+  #[serde(default)]
+  pub boats: Vec<AddBoatRequest>,
+  #[serde(default)]
+  pub boat_assignment: BoatAssignment,
+  /// Overrides the club's early access tiers for this practice.
+  pub early_access: Option<Vec<EarlyAccess>>,
+  pub lottery: Option<LotteryRequest>
+}
+
+#[derive(Deserialize)]
+pub struct LotteryRequest {
+  /// Defaults to half-way through the signup window.
+  pub draws_at: Option<DateTime<Utc>>,
+  #[serde(default)]
+  pub weighting: LotteryWeighting
 }
 
 #[derive(Deserialize)]
 pub struct SignupRequest {
   pub practice_id: String,
+  pub discord_id: String,
+  #[serde(default)]
+  pub role: Option<BoatRole>
+}
+
+#[derive(Deserialize)]
+pub struct SetQualificationsRequest {
+  pub qualifications: Vec<BoatRole>
+}
+
+#[derive(Deserialize)]
+pub struct CreateSeriesRequest {
+  #[serde(default)]
+  pub practice_type: PracticeType,
+  pub weekdays: Vec<Weekday>,
+  pub start_time: NaiveTime,
+  pub end_time: NaiveTime,
+  pub first_date: NaiveDate,
+  pub last_date: NaiveDate,
+  #[serde(default)]
+  pub skipped: Vec<DateRange>,
+  #[serde(default)]
+  pub create_sheet_tabs: bool
+}
+
+#[derive(Deserialize)]
+pub struct UpdateSeriesRequest {
+  pub weekdays: Option<Vec<Weekday>>,
+  pub start_time: Option<NaiveTime>,
+  pub end_time: Option<NaiveTime>,
+  pub last_date: Option<NaiveDate>,
+  pub skipped: Option<Vec<DateRange>>
+}
+
+#[derive(Deserialize)]
+pub struct CancelPracticeRequest {
+  pub reason: String
+}
+
+#[derive(Deserialize)]
+pub struct SetWaitlistFeedRequest {
+  pub feeds_into: Option<String>
+}
+
+#[derive(Deserialize)]
+pub struct LineupOverrideRequest {
+  pub user_id: String,
+  #[serde(default)]
+  pub boat: usize,
+  pub row: usize,
+  pub side: Side
+}
+
+#[derive(Deserialize)]
+pub struct SetLineupOverridesRequest {
+  pub overrides: Vec<LineupOverrideRequest>
+}
+
+#[derive(Deserialize)]
+pub struct UpdatePaddlingProfileRequest {
+  pub weight_kg: Option<f32>,
+  pub experience: Option<Experience>
+}
+
+#[derive(Clone, Deserialize)]
+pub struct AddBoatRequest {
+  pub name: Option<String>,
+  pub seats_per_side: Option<usize>,
+  pub skill_group: Option<Experience>
+}
+
+#[derive(Deserialize)]
+pub struct AttendanceEntry {
+  pub user_id: String,
+  pub status: AttendanceStatus
+}
+
+#[derive(Deserialize)]
+pub struct RecordAttendanceRequest {
+  pub records: Vec<AttendanceEntry>
+}
+
+#[derive(Deserialize)]
+pub struct CheckInRequest {
+  pub status: AttendanceStatus
+}
+
+#[derive(Deserialize)]
+pub struct SetPenaltyWaiverRequest {
+  pub until: Option<DateTime<Utc>>
+}
+
+#[derive(Deserialize)]
+pub struct SetCompetitiveRosterRequest {
+  pub competitive_roster: bool
+}
+
+#[derive(Deserialize)]
+pub struct CreateSeatOfferRequest {
+  pub discord_id: String,
+  pub practice_id: String,
+  pub to_discord_id: String,
+  pub swap_practice_id: Option<String>
+}
+
+#[derive(Deserialize)]
+pub struct SeatOfferActionRequest {
+  pub discord_id: String
+}
+
+#[derive(Deserialize)]
+pub struct CreateGuestRequest {
+  pub discord_id: String,
+  pub first_name: String,
+  pub last_name: String,
+  pub email: String,
+  pub side: Option<Side>,
+  #[serde(default)]
+  pub waiver_signed: bool
+}
+
+#[derive(Deserialize)]
+pub struct SetGuestWaiverRequest {
+  pub waiver_signed: bool
+}
+
+#[derive(Deserialize)]
+pub struct GuestSignupRequest {
+  pub practice_id: String,
+  pub discord_id: String,
+  pub guest_id: String
+}
+
+#[derive(Deserialize)]
+pub struct SetMembershipRequest {
+  pub status: MembershipStatus,
+  pub plan: Option<MembershipPlan>,
+  pub expires_at: Option<DateTime<Utc>>
+}
+
+#[derive(Deserialize)]
+pub struct ReviewWaiverRequest {
+  pub verified: bool,
+  /// Defaults to a year after verification.
+  pub expires_at: Option<DateTime<Utc>>,
+  pub reason: Option<String>
+}
+
+#[derive(Deserialize)]
+pub struct SetRoleRequest {
+  pub role: UserType
+}
+
+#[derive(Deserialize)]
+pub struct PlaceMemberRequest {
+  pub placement: Placement
+}
+
+#[derive(Deserialize)]
+pub struct ReorderWaitlistRequest {
+  pub user_ids: Vec<String>
+}
+
+#[derive(Deserialize)]
+pub struct SetManualLockRequest {
+  /// `None` goes back to the signup window.
+  pub lock: Option<ManualLock>
+}
+
+#[derive(Deserialize)]
+pub struct UpdateProfileRequest {
+  pub side: Option<Side>,
+  /// An empty name goes back to the name from the form.
+  pub display_name: Option<String>,
+  pub notifications: Option<NotificationSettings>
+}
+
+#[derive(Deserialize)]
+pub struct RelinkDiscordRequest {
+  pub email: String,
+  pub discord_id: String
+}
+
+#[derive(Deserialize)]
+pub struct VerifyRelinkRequest {
+  pub email: String,
+  pub discord_id: String,
+  pub code: String
+}
+
+#[derive(Deserialize)]
+pub struct LinkDiscordRequest {
   pub discord_id: String
 }
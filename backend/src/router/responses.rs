@@ -1,6 +1,13 @@
 use chrono::{DateTime, Utc};
 use serde::Serialize;
 
+use crate::db::{
+  club::AppliedPenalty,
+  practice::{Placement, Practice, PracticeType},
+  seat_offer::SeatOffer,
+  user::{Side, SignupTier},
+};
+
 #[derive(Serialize)]
 pub struct SignupResponse {
   pub success: bool,
@@ -8,11 +15,47 @@ pub struct SignupResponse {
   pub on_waitlist: bool
 }
 
+#[derive(Serialize)]
+pub struct EarlyAccessOpening {
+  pub tier: SignupTier,
+  pub opens_at: DateTime<Utc>
+}
+
 #[derive(Serialize)]
 pub struct PracticeStartInfo{
   pub practice_id: String,
+  pub practice_type: PracticeType,
   pub start_time: DateTime<Utc>,
-  pub end_time: DateTime<Utc>
+  pub end_time: DateTime<Utc>,
+  pub signup_opens_at: DateTime<Utc>,
+  pub early_access: Vec<EarlyAccessOpening>,
+  /// Set while signups are collected for a lottery.
+  pub lottery_draws_at: Option<DateTime<Utc>>
+}
+
+impl From<&Practice> for PracticeStartInfo {
+  fn from(practice: &Practice) -> Self {
+    Self {
+      practice_id: practice.id.unwrap().to_string(),
+      practice_type: practice.practice_type,
+      start_time: practice.start_time,
+      end_time: practice.end_time,
+      signup_opens_at: practice.signup_opens_at,
+      early_access: practice
+        .early_access
+        .iter()
+        .map(|early| EarlyAccessOpening {
+          tier: early.tier,
+          opens_at: early.opens_at(practice.signup_opens_at)
+        })
+        .collect(),
+      lottery_draws_at: practice
+        .lottery
+        .as_ref()
+        .filter(|lottery| lottery.is_open())
+        .map(|lottery| lottery.draws_at)
+    }
+  }
 }
 
 #[derive(Serialize)]
@@ -20,3 +63,55 @@ pub struct WaitlistTransferNotification {
   pub practice: PracticeStartInfo,
   pub discord_id: String
 }
+
+#[derive(Serialize)]
+pub struct DirectMessageNotification {
+  pub discord_id: String,
+  pub message: String
+}
+
+#[derive(Serialize)]
+pub struct WaitlistFeedResponse {
+  pub practice_id: String,
+  pub feeds_into: Option<String>,
+  pub fed_by: Vec<String>
+}
+
+#[derive(Serialize)]
+pub struct PenaltyStatusResponse {
+  pub penalty: Option<AppliedPenalty>,
+  pub waived_until: Option<DateTime<Utc>>
+}
+
+/// A practice a member is on and where they are on it.
+#[derive(Serialize)]
+pub struct MemberPractice {
+  pub practice: PracticeStartInfo,
+  pub placement: Placement,
+  /// `None` for role seats and lottery entries.
+  pub side: Option<Side>,
+  pub on_waitlist: bool,
+  /// Counted from 1 among the members waiting on the same side.
+  pub waitlist_position: Option<usize>,
+  /// Signups and unregistrations close then, unless an exec locked the
+  /// practice by hand.
+  pub signup_closes_at: DateTime<Utc>,
+  pub locked: bool,
+  pub cancelled: bool,
+  /// Offers involving this practice that the member made or received.
+  pub seat_offers: Vec<PendingSeatOffer>
+}
+
+/// A seat offer waiting on an answer. It can be accepted until the first of
+/// the practices it involves starts.
+#[derive(Clone, Serialize)]
+pub struct PendingSeatOffer {
+  pub offer: SeatOffer,
+  pub claim_by: DateTime<Utc>
+}
+
+#[derive(Serialize)]
+pub struct MemberPracticesResponse {
+  pub upcoming: Vec<MemberPractice>,
+  pub recent: Vec<MemberPractice>
+}
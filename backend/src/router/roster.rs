@@ -0,0 +1,217 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono_tz::America::New_York;
+use mongodb::bson::oid::ObjectId;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::{
+    db::{
+        audit::{AuditAction, RosterChange},
+        db::DB,
+        practice::Practice,
+        user::{Permission, Side},
+    },
+    notifications::notify_users,
+};
+
+use super::{
+    auth::Actor,
+    requests::{PlaceMemberRequest, ReorderWaitlistRequest, SetManualLockRequest},
+};
+
+/// How many times a roster change reads the practice again when someone
+/// else wrote it first.
+const MAX_WRITE_ATTEMPTS: usize = 3;
+
+/// Reads a practice, applies `edit` to it and writes the roster, starting
+/// over on the latest version when someone else wrote it in between. The
+/// outer error is for reading and writing, the inner one is `edit`'s own.
+pub(crate) async fn edit_roster<T, E>(
+    db: &DB,
+    practice_id: ObjectId,
+    change: &RosterChange,
+    mut edit: impl FnMut(&mut Practice) -> Result<T, E>,
+) -> Result<Result<(Practice, T), E>, String> {
+    for _ in 0..MAX_WRITE_ATTEMPTS {
+        let mut practice = db
+            .get_practice(practice_id)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or("Practice not found")?;
+
+        let value = match edit(&mut practice) {
+            Ok(value) => value,
+            Err(e) => return Ok(Err(e)),
+        };
+        if db
+            .update_roster(&mut practice, change)
+            .await
+            .map_err(|e| e.to_string())?
+        {
+            return Ok(Ok((practice, value)));
+        }
+        info!("Practice {} changed while editing its roster, retrying", practice_id);
+    }
+    Err("The practice is changing too quickly, try again in a moment".to_string())
+}
+
+fn practice_date(practice: &Practice) -> String {
+    practice
+        .start_time
+        .with_timezone(&New_York)
+        .format("%A, %B %d at %I:%M %p")
+        .to_string()
+}
+
+/// Puts a member in a specific seat, role seat or waitlist spot, moving them
+/// if they're already on the practice. Signup windows don't apply.
+pub(crate) async fn place_member(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path((practice_id, user_id)): Path<(String, String)>,
+    Json(req): Json<PlaceMemberRequest>,
+) -> Result<Json<Practice>, String> {
+    actor.require(Permission::EditRosters)?;
+
+    let practice_id = ObjectId::parse_str(&practice_id).map_err(|e| e.to_string())?;
+    let user_id = ObjectId::parse_str(&user_id).map_err(|e| e.to_string())?;
+    let user = db
+        .get_user(user_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("User not found")?;
+
+    let change = actor.change(AuditAction::AdminOverride).about(user_id);
+    let (practice, ()) = edit_roster(&db, practice_id, &change, |practice| {
+        practice.place_member(&user, &req.placement)
+    })
+    .await?
+    .map_err(|e| e.to_string())?;
+    info!(
+        "{:?} placed {} at {:?} on practice {:?}",
+        actor.user.id, user_id, req.placement, practice.id
+    );
+
+    let list = if req.placement.is_seated() {
+        "main list"
+    } else {
+        "waitlist"
+    };
+    notify_users(
+        &db,
+        [user_id],
+        &format!(
+            "An exec put you on the {} for the practice on {}",
+            list,
+            practice_date(&practice)
+        ),
+    )
+    .await;
+
+    Ok(Json(practice))
+}
+
+/// Takes anyone off a practice, guests included. The head of the waitlist
+/// gets the seat as with any other unregistration.
+pub(crate) async fn remove_member(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path((practice_id, user_id)): Path<(String, String)>,
+) -> Result<Json<Practice>, String> {
+    actor.require(Permission::EditRosters)?;
+
+    let practice_id = ObjectId::parse_str(&practice_id).map_err(|e| e.to_string())?;
+    let user_id = ObjectId::parse_str(&user_id).map_err(|e| e.to_string())?;
+
+    let change = actor.change(AuditAction::AdminOverride).about(user_id);
+    let (practice, promoted) = edit_roster(&db, practice_id, &change, |practice| {
+        if practice.is_cancelled() {
+            return Err("Practice has been cancelled".to_string());
+        }
+        practice.remove_by_id(user_id).map_err(|e| e.to_string())
+    })
+    .await??;
+    info!(
+        "{:?} removed {} from practice {:?}",
+        actor.user.id, user_id, practice.id
+    );
+
+    let date = practice_date(&practice);
+    notify_users(
+        &db,
+        [user_id],
+        &format!("An exec took you off the practice on {}", date),
+    )
+    .await;
+    notify_users(
+        &db,
+        promoted,
+        &format!(
+            "A seat opened up, you're now on the main list for the practice on {}",
+            date
+        ),
+    )
+    .await;
+
+    Ok(Json(practice))
+}
+
+pub(crate) async fn reorder_waitlist(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path((practice_id, side)): Path<(String, Side)>,
+    Json(req): Json<ReorderWaitlistRequest>,
+) -> Result<Json<Practice>, String> {
+    actor.require(Permission::EditRosters)?;
+
+    let practice_id = ObjectId::parse_str(&practice_id).map_err(|e| e.to_string())?;
+    let order = req
+        .user_ids
+        .iter()
+        .map(|id| ObjectId::parse_str(id).map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let change = actor.change(AuditAction::AdminOverride);
+    let (practice, ()) = edit_roster(&db, practice_id, &change, |practice| {
+        practice.reorder_waitlist(&side, &order)
+    })
+    .await?
+    .map_err(|e| e.to_string())?;
+    info!(
+        "{:?} reordered the {:?} waitlist of practice {:?}",
+        actor.user.id, side, practice.id
+    );
+
+    Ok(Json(practice))
+}
+
+/// Locks or reopens signups by hand, or goes back to the signup window.
+pub(crate) async fn set_manual_lock(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(practice_id): Path<String>,
+    Json(req): Json<SetManualLockRequest>,
+) -> Result<Json<Practice>, String> {
+    actor.require(Permission::EditRosters)?;
+
+    let practice_id = ObjectId::parse_str(&practice_id).map_err(|e| e.to_string())?;
+
+    let change = actor.change(AuditAction::AdminOverride);
+    let (practice, ()) = edit_roster(&db, practice_id, &change, |practice| {
+        if practice.is_cancelled() {
+            return Err("Practice has been cancelled".to_string());
+        }
+        practice.manual_lock = req.lock;
+        Ok(())
+    })
+    .await??;
+    info!(
+        "{:?} set the lock on practice {:?} to {:?}",
+        actor.user.id, practice.id, practice.manual_lock
+    );
+
+    Ok(Json(practice))
+}
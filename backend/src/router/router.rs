@@ -1,76 +1,455 @@
 use axum::{
-    extract::State,
+    extract::{Path, State},
     middleware,
-    routing::{delete, post},
+    routing::{delete, get, patch, post, put},
     Json, Router,
 };
-use mongodb::bson::oid::ObjectId;
+use chrono::Utc;
+use chrono_tz::America::New_York;
+use mongodb::bson::{doc, oid::ObjectId, to_bson};
 use tower_http::trace::TraceLayer;
-use tracing::info;
+use tracing::{error, info};
 
 use crate::{
     db::{
+        audit::AuditAction,
+        club::SignupEligibility,
         db::DB,
-        practice::{Practice, PracticeError},
+        lottery::Lottery,
+        practice::{Cancellation, Practice, PracticeError},
+        user::Permission,
     },
-    logging::middleware::logging_middleware, router::responses::{PracticeStartInfo, WaitlistTransferNotification},
+    jobs::scheduler::SchedulerManager,
+    logging::middleware::logging_middleware,
+    notifications::notify_users,
+    router::responses::{PracticeStartInfo, WaitlistTransferNotification},
 };
 use std::sync::Arc;
 
 use super::{
-    requests::{CreateDiscordUser, CreatePracticeRequest, SignupRequest},
-    responses::SignupResponse,
+    attendance::{check_in, get_practice_attendance, get_user_attendance, record_attendance},
+    audit::{get_practice_audit, get_user_audit},
+    auth::{Actor, ApiClient},
+    boats::{add_boat, boat_from_request},
+    club::{get_club_settings, update_club_settings},
+    discord::{link_discord, request_relink, unlink_discord, unlink_self, verify_relink},
+    guests::{create_guest, get_guest, set_guest_waiver, signup_guest, unregister_guest},
+    lineup::{get_lineup, set_lineup_overrides},
+    requests::{
+        CancelPracticeRequest, CreateDiscordUser, CreatePracticeRequest, SetWaitlistFeedRequest,
+        SignupRequest,
+    },
+    responses::{SignupResponse, WaitlistFeedResponse},
+    seat_offers::{
+        accept_seat_offer, cancel_seat_offer, create_seat_offer, decline_seat_offer,
+        get_seat_offer,
+    },
+    roster::{edit_roster, place_member, remove_member, reorder_waitlist, set_manual_lock},
+    series::{create_series, update_series},
+    state::AppState,
+    users::{
+        get_me, get_member_practices, get_penalty_status, review_waiver, revoke_role,
+        set_competitive_roster, set_membership, set_penalty_waiver, set_qualifications, set_role,
+        update_me, update_paddling_profile,
+    },
 };
 
-pub fn create_router(db: Arc<DB>) -> Router {
+/// Longest a single practice may run.
+const MAX_PRACTICE_MINUTES: i64 = 24 * 60;
+
+pub fn create_router(state: AppState) -> Router {
     Router::new()
         .route("/register", post(register_discord_user))
+        .route("/register/relink", post(request_relink))
+        .route("/register/relink/verify", post(verify_relink))
         .route("/practice", post(create_practice))
         .route("/practice/signup", post(signup_for_practice))
         .route("/practice/unregister", delete(unregister_for_practice))
+        .route(
+            "/practice/guests",
+            post(signup_guest).delete(unregister_guest),
+        )
+        .route("/practice/:id/cancel", post(cancel_practice))
+        .route("/practice/:id/boats", post(add_boat))
+        .route("/practice/:id/lottery", get(get_lottery))
+        .route(
+            "/practice/:id/attendance",
+            get(get_practice_attendance).post(record_attendance),
+        )
+        .route("/practice/:id/attendance/:user_id", put(check_in))
+        .route(
+            "/practice/:id/waitlist-feed",
+            get(get_waitlist_feed).put(set_waitlist_feed),
+        )
+        .route("/practice/:id/audit", get(get_practice_audit))
+        .route("/practice/:id/lineup", get(get_lineup))
+        .route("/practice/:id/lineup/overrides", put(set_lineup_overrides))
+        .route(
+            "/practice/:id/roster/:user_id",
+            put(place_member).delete(remove_member),
+        )
+        .route("/practice/:id/waitlist/:side", put(reorder_waitlist))
+        .route("/practice/:id/lock", put(set_manual_lock))
+        .route("/seat-offers", post(create_seat_offer))
+        .route("/seat-offers/:id", get(get_seat_offer))
+        .route("/seat-offers/:id/accept", post(accept_seat_offer))
+        .route("/seat-offers/:id/decline", post(decline_seat_offer))
+        .route("/seat-offers/:id/cancel", post(cancel_seat_offer))
+        .route("/series", post(create_series))
+        .route("/series/:id", patch(update_series))
+        .route("/guests", post(create_guest))
+        .route("/guests/:id", get(get_guest))
+        .route("/guests/:id/waiver", put(set_guest_waiver))
+        .route("/me", get(get_me).patch(update_me))
+        .route("/me/discord", delete(unlink_self))
+        .route("/users/:id/practices", get(get_member_practices))
+        // Kept for bot versions that still call the old path
+        .route(
+            "/users/discord/:discord_id/practices",
+            get(get_member_practices),
+        )
+        .route("/users/:id/qualifications", put(set_qualifications))
+        .route("/users/:id/paddling", patch(update_paddling_profile))
+        .route("/users/:id/attendance", get(get_user_attendance))
+        .route("/users/:id/audit", get(get_user_audit))
+        .route("/users/:id/penalty", get(get_penalty_status))
+        .route("/users/:id/penalty-waiver", put(set_penalty_waiver))
+        .route("/users/:id/competitive", put(set_competitive_roster))
+        .route("/users/:id/membership", put(set_membership))
+        .route("/users/:id/waiver", put(review_waiver))
+        .route("/users/:id/role", put(set_role).delete(revoke_role))
+        .route("/users/:id/discord", put(link_discord).delete(unlink_discord))
+        .route(
+            "/club/settings",
+            get(get_club_settings).put(update_club_settings),
+        )
         .layer(middleware::from_fn(logging_middleware))
         .layer(TraceLayer::new_for_http())
-        .with_state(db)
+        .with_state(state)
 }
 
 async fn register_discord_user(
+    _client: ApiClient,
     State(db): State<Arc<DB>>,
     Json(req): Json<CreateDiscordUser>,
 ) -> Result<Json<String>, String> {
-    let mut user = db
+    let user = db
         .get_user_by_email(&req.email)
         .await
         .map_err(|e| e.to_string())?
         .ok_or("User not found with given email")?;
 
-    return match user.discord_id {
-        Some(_) => Err("Discord id already associated to email".to_string()),
+    match user.discord_id {
+        Some(_) => Err(
+            "Discord id already associated to email, use /relink to move it to this account"
+                .to_string(),
+        ),
         None => {
-            user.discord_id = Some(req.discord_id);
-            info!("updated mongo object: {:?}", user);
-            db.update_user(&user).await;
+            let user_id = user.id.ok_or("User has no ID")?;
+            db.set_discord_id(user_id, Some(&req.discord_id))
+                .await
+                .map_err(|e| e.to_string())?;
+            info!("Linked user {} to Discord account {}", user_id, req.discord_id);
             Ok(Json(
                 "Successfully registerd discord id to user".to_string(),
             ))
         }
-    };
+    }
 }
 
 async fn create_practice(
+    actor: Actor,
     State(db): State<Arc<DB>>,
+    State(scheduler): State<Arc<SchedulerManager>>,
     Json(req): Json<CreatePracticeRequest>,
 ) -> Result<Json<Practice>, String> {
-    let practice = Practice::new(req.date, req.start_time);
-    db.create_practice(&practice)
+    actor.require(Permission::SchedulePractices)?;
+
+    let end_time = match (req.end_time, req.duration_minutes) {
+        (Some(_), Some(_)) => {
+            return Err("Specify either end_time or duration_minutes, not both".to_string())
+        }
+        (Some(end_time), None) => end_time,
+        (None, Some(minutes)) => {
+            if !(1..=MAX_PRACTICE_MINUTES).contains(&minutes) {
+                return Err(format!(
+                    "duration_minutes must be between 1 and {}",
+                    MAX_PRACTICE_MINUTES
+                ));
+            }
+            chrono::Duration::try_minutes(minutes)
+                .and_then(|duration| req.start_time.checked_add_signed(duration))
+                .ok_or("Practice end time is out of range")?
+        }
+        (None, None) => req.start_time + req.practice_type.default_duration(),
+    };
+    if end_time <= req.start_time {
+        return Err("Practice must end after it starts".to_string());
+    }
+    if end_time - req.start_time > chrono::Duration::minutes(MAX_PRACTICE_MINUTES) {
+        return Err(format!(
+            "Practices can't run longer than {} hours",
+            MAX_PRACTICE_MINUTES / 60
+        ));
+    }
+
+    let settings = db.get_club_settings().await.map_err(|e| e.to_string())?;
+    let mut practice = Practice::new(
+        req.practice_type,
+        req.date,
+        req.start_time,
+        end_time,
+        &settings.signup_window(req.practice_type),
+    )
+    .map_err(|e| e.to_string())?;
+    if let Some(opens_at) = req.signup_opens_at {
+        practice.signup_opens_at = opens_at;
+    }
+    if let Some(closes_at) = req.signup_closes_at {
+        practice.signup_closes_at = closes_at;
+    }
+    if let Some(transfer_at) = req.waitlist_transfer_at {
+        practice.waitlist_transfer_at = transfer_at;
+    }
+
+    // Early access opens relative to the final signup opening, and may move
+    // the waitlist transfer ahead of it
+    if let Some(early_access) = &req.early_access {
+        for early in early_access {
+            early.validate()?;
+        }
+    }
+    practice.set_early_access(req.early_access.unwrap_or(settings.early_access));
+    if !req.boats.is_empty() {
+        practice.boats.clear();
+        for boat in req.boats {
+            let boat = boat_from_request(&practice, boat)?;
+            practice.add_boat(boat);
+        }
+    }
+    practice.boat_assignment = req.boat_assignment;
+    if let Some(lottery) = req.lottery {
+        let draws_at = lottery.draws_at.unwrap_or(
+            practice.signup_opens_at + (practice.signup_closes_at - practice.signup_opens_at) / 2,
+        );
+        practice.lottery = Some(Lottery::new(draws_at, lottery.weighting));
+    }
+    practice
+        .validate_signup_window()
+        .map_err(|e| e.to_string())?;
+    let practice_id = db
+        .create_practice(&practice)
+        .await
+        .map_err(|e| e.to_string())?;
+    practice.id = Some(practice_id);
+
+    db.link_waitlist_feed(&mut practice)
         .await
         .map_err(|e| e.to_string())?;
+
+    scheduler
+        .schedule_practice(&practice)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(Json(practice))
+}
+
+async fn cancel_practice(
+    actor: Actor,
+    State(state): State<AppState>,
+    Path(practice_id): Path<String>,
+    Json(req): Json<CancelPracticeRequest>,
+) -> Result<Json<Practice>, String> {
+    actor.require(Permission::SchedulePractices)?;
+
+    info!("Processing cancel request for practice_id {}", practice_id);
+
+    let practice_id = ObjectId::parse_str(&practice_id).map_err(|e| e.to_string())?;
+
+    let mut practice = state
+        .db
+        .get_practice(practice_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Practice not found")?;
+
+    if practice.is_cancelled() {
+        return Err("Practice is already cancelled".to_string());
+    }
+
+    cancel_and_notify(&state, &mut practice, req.reason).await?;
     Ok(Json(practice))
 }
 
+/// Cancels a practice while keeping it for history: its jobs are dropped,
+/// everyone signed up is told why and the sheet tab is marked.
+pub(crate) async fn cancel_and_notify(
+    state: &AppState,
+    practice: &mut Practice,
+    reason: String,
+) -> Result<(), String> {
+    let practice_id = practice.id.ok_or("Practice has no ID")?;
+
+    let cancellation = Cancellation {
+        reason,
+        cancelled_at: Utc::now(),
+    };
+    state
+        .db
+        .update_practice_fields(
+            practice,
+            doc! {"cancellation": to_bson(&cancellation).map_err(|e| e.to_string())?},
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    state
+        .scheduler
+        .unschedule_practice(practice_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Waitlists that would have carried over into this practice skip ahead to
+    // the one it feeds into instead
+    for mut source in state
+        .db
+        .get_waitlist_sources(practice)
+        .await
+        .map_err(|e| e.to_string())?
+    {
+        state
+            .db
+            .set_waitlist_feed(&mut source, practice.waitlist_feeds_into)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    let message = format!(
+        "Practice on {} has been cancelled: {}",
+        practice
+            .start_time
+            .with_timezone(&New_York)
+            .format("%A, %B %d at %I:%M %p"),
+        practice.cancellation.as_ref().unwrap().reason
+    );
+    notify_users(&state.db, practice.participants(), &message).await;
+
+    if let Err(e) = state.practice_client.mark_practice_tab_cancelled(practice).await {
+        error!("Failed to mark sheet tab of practice {} as cancelled: {}", practice_id, e);
+    }
+
+    info!("Cancelled practice {}", practice_id);
+    Ok(())
+}
+
+/// The lottery of a practice, including the seed and weights of its draw so
+/// the result can be checked.
+async fn get_lottery(
+    _client: ApiClient,
+    State(db): State<Arc<DB>>,
+    Path(practice_id): Path<String>,
+) -> Result<Json<Lottery>, String> {
+    let practice_id = ObjectId::parse_str(&practice_id).map_err(|e| e.to_string())?;
+
+    let practice = db
+        .get_practice(practice_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Practice not found")?;
+
+    practice
+        .lottery
+        .map(Json)
+        .ok_or("Practice has no lottery".to_string())
+}
+
+async fn get_waitlist_feed(
+    _client: ApiClient,
+    State(db): State<Arc<DB>>,
+    Path(practice_id): Path<String>,
+) -> Result<Json<WaitlistFeedResponse>, String> {
+    let practice_id = ObjectId::parse_str(&practice_id).map_err(|e| e.to_string())?;
+
+    let practice = db
+        .get_practice(practice_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Practice not found")?;
+
+    waitlist_feed_response(&db, &practice).await.map(Json)
+}
+
+async fn set_waitlist_feed(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(practice_id): Path<String>,
+    Json(req): Json<SetWaitlistFeedRequest>,
+) -> Result<Json<WaitlistFeedResponse>, String> {
+    actor.require(Permission::SchedulePractices)?;
+
+    let practice_id = ObjectId::parse_str(&practice_id).map_err(|e| e.to_string())?;
+
+    let mut practice = db
+        .get_practice(practice_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Practice not found")?;
+
+    let feeds_into = match req.feeds_into {
+        Some(target_id) => {
+            let target_id = ObjectId::parse_str(&target_id).map_err(|e| e.to_string())?;
+            let target = db
+                .get_practice(target_id)
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or("Target practice not found")?;
+
+            if target.start_time <= practice.start_time {
+                return Err("Waitlist can only feed into a later practice".to_string());
+            }
+            Some(target_id)
+        }
+        None => None,
+    };
+
+    info!(
+        "Setting waitlist of practice {} to feed into {:?}",
+        practice_id, feeds_into
+    );
+    db.set_waitlist_feed(&mut practice, feeds_into)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    waitlist_feed_response(&db, &practice).await.map(Json)
+}
+
+async fn waitlist_feed_response(
+    db: &DB,
+    practice: &Practice,
+) -> Result<WaitlistFeedResponse, String> {
+    let fed_by = db
+        .get_waitlist_sources(practice)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter_map(|source| source.id)
+        .map(|id| id.to_string())
+        .collect();
+
+    Ok(WaitlistFeedResponse {
+        practice_id: practice.id.ok_or("Practice has no ID")?.to_string(),
+        feeds_into: practice.waitlist_feeds_into.map(|id| id.to_string()),
+        fed_by,
+    })
+}
+
 async fn signup_for_practice(
+    actor: Actor,
     State(db): State<Arc<DB>>,
     Json(req): Json<SignupRequest>,
 ) -> Result<Json<SignupResponse>, String> {
+    actor.require_self_or(&req.discord_id, Permission::EditRosters)?;
+
     info!(
         "Processing signup request for practice_id {}, discord_id: {}",
         req.practice_id, req.discord_id
@@ -78,7 +457,7 @@ async fn signup_for_practice(
 
     let practice_id = ObjectId::parse_str(&req.practice_id).map_err(|e| e.to_string())?;
 
-    let mut practice = db
+    let practice = db
         .get_practice(practice_id)
         .await
         .map_err(|e| e.to_string())?
@@ -90,31 +469,137 @@ async fn signup_for_practice(
         .map_err(|e| e.to_string())?
         .ok_or("User not found")?;
 
-    if practice.is_locked() {
+    if practice.is_cancelled() {
         return Ok(Json(SignupResponse {
             success: false,
-            message: "Practice is locked until one hour before start time".to_string(),
+            message: "Practice has been cancelled".to_string(),
             on_waitlist: false,
         }));
     }
 
-    let side = practice.determine_side(&user.side);
+    let tiers = user.signup_tiers();
+    if practice.is_locked_for(&tiers) {
+        return Ok(Json(SignupResponse {
+            success: false,
+            message: if Utc::now() < practice.signup_opens_for(&tiers) {
+                format!(
+                    "Signups open on {}",
+                    practice
+                        .signup_opens_for(&tiers)
+                        .with_timezone(&New_York)
+                        .format("%A, %B %d at %I:%M %p")
+                )
+            } else {
+                "Signups for this practice are closed".to_string()
+            },
+            on_waitlist: false,
+        }));
+    }
 
-    match practice
-        .add_participant(&req.discord_id, &side, db.clone())
+    let now = Utc::now();
+    if let Some(message) = user.waiver.signup_blocker(now) {
+        return Ok(Json(SignupResponse {
+            success: false,
+            message,
+            on_waitlist: false,
+        }));
+    }
+
+    let settings = db.get_club_settings().await.map_err(|e| e.to_string())?;
+    let history = db
+        .get_user_attendance(user.id.ok_or("User has no ID")?)
         .await
+        .map_err(|e| e.to_string())?;
+
+    let waitlist_reason = match settings.signup_eligibility(
+        &user,
+        &history,
+        practice.signup_opens_for(&tiers),
+        now,
+    ) {
+        SignupEligibility::Allowed => None,
+        SignupEligibility::WaitlistOnly(reason) => {
+            if req.role.is_some() {
+                return Ok(Json(SignupResponse {
+                    success: false,
+                    message: format!("You can only join the waitlist because {}", reason),
+                    on_waitlist: false,
+                }));
+            }
+            Some(reason)
+        }
+        SignupEligibility::Refused(message) => {
+            return Ok(Json(SignupResponse {
+                success: false,
+                message,
+                on_waitlist: false,
+            }));
+        }
+    };
+
+    let waitlist_only = waitlist_reason.is_some();
+    let change = actor.change(AuditAction::Signup);
+
+    // Role seats aren't drawn, everything else waits for the lottery
+    if practice.lottery_is_open() && req.role.is_none() {
+        return match edit_roster(&db, practice_id, &change, |practice| {
+            practice.enter_lottery(&user, waitlist_only)
+        })
+        .await?
+        {
+            Ok((practice, ())) => {
+                Ok(Json(SignupResponse {
+                    success: true,
+                    message: format!(
+                        "Entered the lottery, seats are drawn on {}",
+                        practice
+                            .lottery
+                            .as_ref()
+                            .unwrap()
+                            .draws_at
+                            .with_timezone(&New_York)
+                            .format("%A, %B %d at %I:%M %p")
+                    ),
+                    on_waitlist: false,
+                }))
+            }
+            Err(e @ PracticeError::AlreadySignedUp) => Ok(Json(SignupResponse {
+                success: false,
+                message: e.to_string(),
+                on_waitlist: false,
+            })),
+            Err(e) => Err(e.to_string()),
+        };
+    }
+
+    let side = practice.determine_side(&user.side);
+
+    match edit_roster(&db, practice_id, &change, |practice| {
+        practice.add_participant(&user, &side, req.role.as_ref(), waitlist_only)
+    })
+    .await?
     {
-        Ok(main) => {
-            db.update_practice(&practice)
-                .await
-                .map_err(|e| e.to_string())?;
+        Ok((practice, main)) => {
+            // Only worth saying which boat when there's a choice
+            let boat = user
+                .id
+                .and_then(|user_id| practice.seat_of(user_id))
+                .filter(|_| practice.boats.len() > 1)
+                .map(|(boat, _)| format!(" in {}", practice.boats[boat].name))
+                .unwrap_or_default();
 
             Ok(Json(SignupResponse {
                 success: true,
-                message: if main {
-                    format!("Signed up on main list")
-                } else {
-                    format!("Signed up for waitlist")
+                message: match (&req.role, main) {
+                    (Some(role), _) => format!("Signed up as {:?}", role),
+                    (None, true) => format!("Signed up on main list{}", boat),
+                    (None, false) => match waitlist_reason {
+                        Some(reason) => format!(
+                            "Signed up for waitlist, you can only join the waitlist because {}",
+                            reason
+                        ),
+                        None => "Signed up for waitlist".to_string(),
+                    },
                 },
                 on_waitlist: !main,
             }))
@@ -125,14 +610,27 @@ async fn signup_for_practice(
             message: format!("{:?} side main list and waitlist are full", side),
             on_waitlist: false,
         })),
+        Err(
+            e @ (PracticeError::AlreadySignedUp
+            | PracticeError::NoRoleSeat(_)
+            | PracticeError::RoleTaken(_)
+            | PracticeError::NotQualified(_)),
+        ) => Ok(Json(SignupResponse {
+            success: false,
+            message: e.to_string(),
+            on_waitlist: false,
+        })),
         Err(e) => Err(e.to_string()),
     }
 }
 
     async fn unregister_for_practice(
+        actor: Actor,
         State(db): State<Arc<DB>>,
         Json(req): Json<SignupRequest>,
     ) -> Result<Json<SignupResponse>, String> {
+        actor.require_self_or(&req.discord_id, Permission::EditRosters)?;
+
         info!(
             "Processing unregister request for practice_id {}, discord_id: {}",
             req.practice_id, req.discord_id
@@ -140,23 +638,26 @@ async fn signup_for_practice(
 
         let practice_id = ObjectId::parse_str(&req.practice_id).map_err(|e| e.to_string())?;
 
-        let mut practice = db
-            .get_practice(practice_id)
+        let Some(user) = db
+            .get_user_by_discord_id(&req.discord_id)
             .await
             .map_err(|e| e.to_string())?
-            .ok_or("Practice not found")?;
+        else {
+            return Ok(Json(SignupResponse {
+                success: false,
+                message: "User not registered for this practice".to_string(),
+                on_waitlist: false,
+            }));
+        };
 
         // Remove participant and get waitlist user if any
-        match practice
-            .remove_participant(&req.discord_id, db.clone())
-            .await
+        let change = actor.change(AuditAction::Unregister);
+        match edit_roster(&db, practice_id, &change, |practice| {
+            practice.remove_participant(&user)
+        })
+        .await?
         {
-            Ok(maybe_waitlist_user) => {
-                // Update practice in database
-                db.update_practice(&practice)
-                    .await
-                    .map_err(|e| e.to_string())?;
-
+            Ok((practice, maybe_waitlist_user)) => {
                 // If someone from waitlist was moved to main list, notify them
                 if let Some(waitlist_user_id) = maybe_waitlist_user {
                     if let Ok(Some(user)) = db.get_user(waitlist_user_id).await {
@@ -164,11 +665,7 @@ async fn signup_for_practice(
                             // Send notification to Discord bot
                             let client = reqwest::Client::new();
                             let notification = WaitlistTransferNotification {
-                                practice: PracticeStartInfo {
-                                    practice_id: practice.id.unwrap().to_string(),
-                                    start_time: practice.start_time,
-                                    end_time: practice.end_time,
-                                },
+                                practice: PracticeStartInfo::from(&practice),
                                 discord_id: discord_id.parse().unwrap_or_default(),
                             };
 
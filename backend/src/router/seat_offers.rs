@@ -0,0 +1,551 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::Utc;
+use chrono_tz::America::New_York;
+use mongodb::bson::oid::ObjectId;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+use crate::{
+    db::{
+        audit::{AuditAction, RosterChange},
+        club::{ClubSettings, SignupEligibility},
+        db::DB,
+        practice::Practice,
+        seat_offer::{SeatOffer, SeatOfferStatus},
+        user::{Permission, User},
+    },
+    notifications::{notify_execs, notify_users},
+};
+
+use super::{
+    auth::Actor,
+    requests::{CreateSeatOfferRequest, SeatOfferActionRequest},
+};
+
+/// How many times accepting an offer re-reads the practices when someone else
+/// changed them in the meantime.
+const MAX_ACCEPT_ATTEMPTS: usize = 3;
+
+/// Offers a seat to another member, or with `swap_practice_id` proposes
+/// trading it for their seat on that practice. Nothing moves until they accept.
+pub(crate) async fn create_seat_offer(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Json(req): Json<CreateSeatOfferRequest>,
+) -> Result<Json<SeatOffer>, String> {
+    actor.require_self_or(&req.discord_id, Permission::EditRosters)?;
+
+    let from = member(&db, &req.discord_id).await?;
+    let to = member(&db, &req.to_discord_id).await?;
+    let (from_id, to_id) = (user_id(&from)?, user_id(&to)?);
+    if from_id == to_id {
+        return Err("You can't offer your seat to yourself".to_string());
+    }
+
+    let practice_id = ObjectId::parse_str(&req.practice_id).map_err(|e| e.to_string())?;
+    let practice = open_practice(&db, practice_id).await?;
+    let seat = practice
+        .seat_ref(from_id)
+        .ok_or("You don't have a seat on this practice")?;
+
+    let swap_practice_id = match &req.swap_practice_id {
+        Some(swap_practice_id) => {
+            let swap_practice_id = ObjectId::parse_str(swap_practice_id).map_err(|e| e.to_string())?;
+            if swap_practice_id == practice_id {
+                return Err("A swap needs two different practices".to_string());
+            }
+
+            let other = open_practice(&db, swap_practice_id).await?;
+            let other_seat = other
+                .seat_ref(to_id)
+                .ok_or("The other member doesn't have a seat on the practice to swap with")?;
+            check_swap(&practice, &other, from_id, to_id)?;
+            other
+                .can_take_seat(&other_seat, &from)
+                .map_err(|e| e.to_string())?;
+
+            Some(swap_practice_id)
+        }
+        None => {
+            if practice.seat_ref(to_id).is_some() {
+                return Err("The other member already has a seat on this practice".to_string());
+            }
+            None
+        }
+    };
+    practice
+        .can_take_seat(&seat, &to)
+        .map_err(|e| format!("The other member can't take your seat: {}", e))?;
+    if to.waiver.signup_blocker(Utc::now()).is_some() {
+        return Err("The other member doesn't have a verified waiver".to_string());
+    }
+
+    let mut offer = SeatOffer {
+        id: None,
+        from_user: from_id,
+        to_user: to_id,
+        practice_id,
+        swap_practice_id,
+        status: SeatOfferStatus::Pending,
+        created_at: Utc::now(),
+        resolved_at: None,
+    };
+    let offer_id = db
+        .create_seat_offer(&offer)
+        .await
+        .map_err(|e| e.to_string())?;
+    offer.id = Some(offer_id);
+    info!("User {} offered their seat on practice {} to {}", from_id, practice_id, to_id);
+
+    let message = match swap_practice_id {
+        Some(swap_practice_id) => {
+            let other = open_practice(&db, swap_practice_id).await?;
+            format!(
+                "{} wants to swap their seat at the practice on {} for yours on {}. Seat offer ID: {}",
+                from.name(),
+                practice_day(&practice),
+                practice_day(&other),
+                offer_id
+            )
+        }
+        None => format!(
+            "{} is offering you their seat at the practice on {}. Seat offer ID: {}",
+            from.name(),
+            practice_day(&practice),
+            offer_id
+        ),
+    };
+    notify_users(&db, [to_id], &message).await;
+
+    Ok(Json(offer))
+}
+
+pub(crate) async fn get_seat_offer(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(offer_id): Path<String>,
+) -> Result<Json<SeatOffer>, String> {
+    let offer_id = ObjectId::parse_str(&offer_id).map_err(|e| e.to_string())?;
+
+    let offer = db
+        .get_seat_offer(offer_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Seat offer not found")?;
+    if actor.user.id != Some(offer.from_user) {
+        actor.require_user_or(offer.to_user, Permission::EditRosters)?;
+    }
+
+    Ok(Json(offer))
+}
+
+/// Moves the seats. Both practices are re-read and checked again since the
+/// offer was made, and only written if nobody changed them in between.
+pub(crate) async fn accept_seat_offer(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(offer_id): Path<String>,
+    Json(req): Json<SeatOfferActionRequest>,
+) -> Result<Json<SeatOffer>, String> {
+    actor.require_self_or(&req.discord_id, Permission::EditRosters)?;
+
+    let mut offer = pending_offer(&db, &offer_id).await?;
+    let to = member(&db, &req.discord_id).await?;
+    if to.id != Some(offer.to_user) {
+        return Err("Only the member the seat was offered to can accept it".to_string());
+    }
+    let from = db
+        .get_user(offer.from_user)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("User not found")?;
+
+    if let Some(message) = to.waiver.signup_blocker(Utc::now()) {
+        return Err(message);
+    }
+    if offer.swap_practice_id.is_some() && from.waiver.signup_blocker(Utc::now()).is_some() {
+        return Err("The other member's waiver is no longer valid".to_string());
+    }
+
+    // Taking a seat is the same as signing up for it, penalties included
+    let settings = db.get_club_settings().await.map_err(|e| e.to_string())?;
+    let practice = open_practice(&db, offer.practice_id).await?;
+    check_eligible(&db, &settings, &to, &practice).await?;
+    if let Some(swap_practice_id) = offer.swap_practice_id {
+        let other = open_practice(&db, swap_practice_id).await?;
+        check_eligible(&db, &settings, &from, &other)
+            .await
+            .map_err(|_| "The other member can't take a seat on your practice right now".to_string())?;
+    }
+
+    // Claiming the offer first stops a second accept from handing the seat
+    // over again while the practices are being written
+    transition(&db, &mut offer, SeatOfferStatus::Applying).await?;
+    let change = actor.change(AuditAction::Transfer);
+    if let Err(e) = apply_with_retries(&db, &offer, &from, &to, &change).await {
+        if let Err(released) = transition(&db, &mut offer, SeatOfferStatus::Pending).await {
+            error!("Failed to release seat offer {}: {}", offer_id, released);
+        }
+        return Err(e);
+    }
+
+    transition(&db, &mut offer, SeatOfferStatus::Accepted).await?;
+    info!("Seat offer {} accepted", offer_id);
+
+    let practice = open_practice(&db, offer.practice_id).await?;
+    match offer.swap_practice_id {
+        Some(swap_practice_id) => {
+            let other = open_practice(&db, swap_practice_id).await?;
+            notify_users(
+                &db,
+                [offer.from_user],
+                &format!(
+                    "{} accepted your swap: you're now on the practice on {} instead of {}",
+                    to.name(),
+                    practice_day(&other),
+                    practice_day(&practice)
+                ),
+            )
+            .await;
+            notify_users(
+                &db,
+                [offer.to_user],
+                &format!(
+                    "Swap done: you're now on the practice on {} instead of {}",
+                    practice_day(&practice),
+                    practice_day(&other)
+                ),
+            )
+            .await;
+        }
+        None => {
+            notify_users(
+                &db,
+                [offer.from_user],
+                &format!(
+                    "{} took your seat at the practice on {}, you're no longer signed up",
+                    to.name(),
+                    practice_day(&practice)
+                ),
+            )
+            .await;
+            notify_users(
+                &db,
+                [offer.to_user],
+                &format!("You're now on the main list for the practice on {}", practice_day(&practice)),
+            )
+            .await;
+        }
+    }
+
+    Ok(Json(offer))
+}
+
+pub(crate) async fn decline_seat_offer(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(offer_id): Path<String>,
+    Json(req): Json<SeatOfferActionRequest>,
+) -> Result<Json<SeatOffer>, String> {
+    actor.require_self_or(&req.discord_id, Permission::EditRosters)?;
+
+    let mut offer = pending_offer(&db, &offer_id).await?;
+    let to = member(&db, &req.discord_id).await?;
+    if to.id != Some(offer.to_user) {
+        return Err("Only the member the seat was offered to can decline it".to_string());
+    }
+
+    transition(&db, &mut offer, SeatOfferStatus::Declined).await?;
+    info!("Seat offer {} declined", offer_id);
+
+    notify_users(
+        &db,
+        [offer.from_user],
+        &format!("{} declined your seat offer", to.name()),
+    )
+    .await;
+
+    Ok(Json(offer))
+}
+
+pub(crate) async fn cancel_seat_offer(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(offer_id): Path<String>,
+    Json(req): Json<SeatOfferActionRequest>,
+) -> Result<Json<SeatOffer>, String> {
+    actor.require_self_or(&req.discord_id, Permission::EditRosters)?;
+
+    let mut offer = pending_offer(&db, &offer_id).await?;
+    let from = member(&db, &req.discord_id).await?;
+    if from.id != Some(offer.from_user) {
+        return Err("Only the member who made the offer can cancel it".to_string());
+    }
+
+    transition(&db, &mut offer, SeatOfferStatus::Cancelled).await?;
+    info!("Seat offer {} cancelled", offer_id);
+
+    notify_users(
+        &db,
+        [offer.to_user],
+        &format!("{} withdrew their seat offer", from.name()),
+    )
+    .await;
+
+    Ok(Json(offer))
+}
+
+async fn apply_with_retries(
+    db: &DB,
+    offer: &SeatOffer,
+    from: &User,
+    to: &User,
+    change: &RosterChange,
+) -> Result<(), String> {
+    for _ in 0..MAX_ACCEPT_ATTEMPTS {
+        if apply_offer(db, offer, from, to, change).await? {
+            return Ok(());
+        }
+        info!("Practice changed while accepting seat offer {:?}, retrying", offer.id);
+    }
+    Err("The practice is changing too quickly, try again in a moment".to_string())
+}
+
+/// Hands the seats over and writes the practices, returning false when one of
+/// them changed since it was read. Without transactions a swap is two writes:
+/// the offer stays `Applying` in between, and a swap whose second write fails
+/// undoes the first one.
+async fn apply_offer(
+    db: &DB,
+    offer: &SeatOffer,
+    from: &User,
+    to: &User,
+    change: &RosterChange,
+) -> Result<bool, String> {
+    let (from_id, to_id) = (user_id(from)?, user_id(to)?);
+
+    let mut practice = open_practice(db, offer.practice_id).await?;
+    let original = practice.clone();
+    let seat = practice
+        .seat_ref(from_id)
+        .ok_or("The seat is no longer available")?;
+
+    let Some(swap_practice_id) = offer.swap_practice_id else {
+        if practice.seat_ref(to_id).is_some() {
+            return Err("You already have a seat on this practice".to_string());
+        }
+        practice.hand_over_seat(&seat, to).map_err(|e| e.to_string())?;
+
+        return db
+            .update_roster(&mut practice, change)
+            .await
+            .map_err(|e| e.to_string());
+    };
+
+    let mut other = open_practice(db, swap_practice_id).await?;
+    let other_seat = other
+        .seat_ref(to_id)
+        .ok_or("You no longer have a seat on the practice to swap")?;
+    check_swap(&practice, &other, from_id, to_id)?;
+
+    practice.hand_over_seat(&seat, to).map_err(|e| e.to_string())?;
+    other
+        .hand_over_seat(&other_seat, from)
+        .map_err(|e| e.to_string())?;
+
+    if !db
+        .update_roster(&mut practice, change)
+        .await
+        .map_err(|e| e.to_string())?
+    {
+        return Ok(false);
+    }
+
+    let second = db
+        .update_roster(&mut other, change)
+        .await
+        .map_err(|e| e.to_string());
+    if let Ok(true) = second {
+        return Ok(true);
+    }
+
+    let mut revert = original;
+    revert.revision = practice.revision;
+    match db.update_roster(&mut revert, change).await {
+        Ok(true) => second,
+        reverted => {
+            error!(
+                "Failed to undo half of seat swap {:?} on practice {}: {:?}",
+                offer.id, offer.practice_id, reverted
+            );
+            notify_execs(
+                db,
+                &format!(
+                    "A seat swap between practices {} and {} only went through on the first one, please check both rosters",
+                    offer.practice_id, swap_practice_id
+                ),
+            )
+            .await;
+            Err("The swap couldn't be completed, the execs have been told".to_string())
+        }
+    }
+}
+
+/// Whether a member's record lets them take a seat on a practice.
+async fn check_eligible(
+    db: &DB,
+    settings: &ClubSettings,
+    user: &User,
+    practice: &Practice,
+) -> Result<(), String> {
+    let history = db
+        .get_user_attendance(user_id(user)?)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    match settings.signup_eligibility(
+        user,
+        &history,
+        practice.signup_opens_for(&user.signup_tiers()),
+        Utc::now(),
+    ) {
+        SignupEligibility::Allowed => Ok(()),
+        SignupEligibility::WaitlistOnly(reason) => {
+            Err(format!("You can only join the waitlist because {}", reason))
+        }
+        SignupEligibility::Refused(message) => Err(message),
+    }
+}
+
+/// Neither member can end up with two seats on the same practice.
+fn check_swap(practice: &Practice, other: &Practice, from_id: ObjectId, to_id: ObjectId) -> Result<(), String> {
+    if other.seat_ref(from_id).is_some() {
+        return Err("You already have a seat on the practice to swap with".to_string());
+    }
+    if practice.seat_ref(to_id).is_some() {
+        return Err("The other member already has a seat on this practice".to_string());
+    }
+    Ok(())
+}
+
+async fn member(db: &DB, discord_id: &str) -> Result<User, String> {
+    db.get_user_by_discord_id(discord_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or(format!("No member linked to Discord account {}", discord_id))
+}
+
+fn user_id(user: &User) -> Result<ObjectId, String> {
+    user.id.ok_or("User has no ID".to_string())
+}
+
+/// A practice whose seats can still change hands.
+async fn open_practice(db: &DB, practice_id: ObjectId) -> Result<Practice, String> {
+    let practice = db
+        .get_practice(practice_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Practice not found")?;
+
+    if practice.is_cancelled() {
+        return Err("Practice has been cancelled".to_string());
+    }
+    if !practice.is_future() {
+        return Err("Practice has already started".to_string());
+    }
+    Ok(practice)
+}
+
+async fn pending_offer(db: &DB, offer_id: &str) -> Result<SeatOffer, String> {
+    let offer_id = ObjectId::parse_str(offer_id).map_err(|e| e.to_string())?;
+
+    let offer = db
+        .get_seat_offer(offer_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Seat offer not found")?;
+
+    if !offer.is_pending() {
+        return Err(format!("Seat offer was already {:?}", offer.status).to_lowercase());
+    }
+    Ok(offer)
+}
+
+/// Moves the offer on from its current status, failing when another request
+/// already did.
+async fn transition(db: &DB, offer: &mut SeatOffer, status: SeatOfferStatus) -> Result<(), String> {
+    let expected = offer.status;
+    offer.status = status;
+    offer.resolved_at = match status {
+        SeatOfferStatus::Pending | SeatOfferStatus::Applying => None,
+        _ => Some(Utc::now()),
+    };
+
+    if db
+        .update_seat_offer(offer, expected)
+        .await
+        .map_err(|e| e.to_string())?
+    {
+        return Ok(());
+    }
+    offer.status = expected;
+    Err("Seat offer was already taken care of by another request".to_string())
+}
+
+/// Settles offers left `Applying` by a restart in the middle of an accept,
+/// going by whether the rosters show the seats as handed over.
+pub(crate) async fn recover_seat_offers(db: &DB) -> Result<(), String> {
+    let offers = db
+        .get_seat_offers_by_status(SeatOfferStatus::Applying)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    for mut offer in offers {
+        let mut practices = vec![(offer.practice_id, offer.from_user, offer.to_user)];
+        if let Some(swap_practice_id) = offer.swap_practice_id {
+            practices.push((swap_practice_id, offer.to_user, offer.from_user));
+        }
+
+        let (mut done, mut untouched) = (true, true);
+        for (practice_id, giver, taker) in practices {
+            let practice = db
+                .get_practice(practice_id)
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or("Practice not found")?;
+            let (gave, took) = (practice.seat_ref(giver).is_none(), practice.seat_ref(taker).is_some());
+            done &= gave && took;
+            untouched &= !gave && !took;
+        }
+
+        let status = if done {
+            SeatOfferStatus::Accepted
+        } else if untouched {
+            SeatOfferStatus::Pending
+        } else {
+            notify_execs(
+                db,
+                &format!(
+                    "A seat offer on practice {} was interrupted halfway, please check the rosters",
+                    offer.practice_id
+                ),
+            )
+            .await;
+            SeatOfferStatus::Cancelled
+        };
+        warn!("Recovered seat offer {:?} as {:?}", offer.id, status);
+        transition(db, &mut offer, status).await?;
+    }
+    Ok(())
+}
+
+fn practice_day(practice: &Practice) -> String {
+    practice
+        .start_time
+        .with_timezone(&New_York)
+        .format("%A, %B %d at %I:%M %p")
+        .to_string()
+}
@@ -0,0 +1,250 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::Utc;
+use chrono_tz::America::New_York;
+use mongodb::bson::oid::ObjectId;
+use tracing::{error, info};
+
+use crate::{
+    db::{practice::Practice, series::PracticeSeries, user::Permission},
+    notifications::notify_users,
+};
+
+use super::{
+    auth::Actor,
+    requests::{CreateSeriesRequest, UpdateSeriesRequest},
+    router::cancel_and_notify,
+    state::AppState,
+};
+
+pub(crate) async fn create_series(
+    actor: Actor,
+    State(state): State<AppState>,
+    Json(req): Json<CreateSeriesRequest>,
+) -> Result<Json<PracticeSeries>, String> {
+    actor.require(Permission::SchedulePractices)?;
+
+    let mut series = PracticeSeries {
+        id: None,
+        practice_type: req.practice_type,
+        weekdays: req.weekdays,
+        start_time: req.start_time,
+        end_time: req.end_time,
+        first_date: req.first_date,
+        last_date: req.last_date,
+        skipped: req.skipped,
+        create_sheet_tabs: req.create_sheet_tabs,
+    };
+    series.validate()?;
+
+    let series_id = state
+        .db
+        .create_series(&series)
+        .await
+        .map_err(|e| e.to_string())?;
+    series.id = Some(series_id);
+
+    info!("Created practice series {}", series_id);
+    sync_series_practices(&state, &series).await?;
+
+    Ok(Json(series))
+}
+
+pub(crate) async fn update_series(
+    actor: Actor,
+    State(state): State<AppState>,
+    Path(series_id): Path<String>,
+    Json(req): Json<UpdateSeriesRequest>,
+) -> Result<Json<PracticeSeries>, String> {
+    actor.require(Permission::SchedulePractices)?;
+
+    let series_id = ObjectId::parse_str(&series_id).map_err(|e| e.to_string())?;
+
+    let mut series = state
+        .db
+        .get_series(series_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Series not found")?;
+
+    if let Some(weekdays) = req.weekdays {
+        series.weekdays = weekdays;
+    }
+    if let Some(start_time) = req.start_time {
+        series.start_time = start_time;
+    }
+    if let Some(end_time) = req.end_time {
+        series.end_time = end_time;
+    }
+    if let Some(last_date) = req.last_date {
+        series.last_date = last_date;
+    }
+    if let Some(skipped) = req.skipped {
+        series.skipped = skipped;
+    }
+    series.validate()?;
+
+    state
+        .db
+        .update_series(&series)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    info!("Updated practice series {}", series_id);
+    sync_series_practices(&state, &series).await?;
+
+    Ok(Json(series))
+}
+
+/// Brings the future practices of a series in line with its definition.
+/// Past practices are left untouched so edits never rewrite history.
+async fn sync_series_practices(state: &AppState, series: &PracticeSeries) -> Result<(), String> {
+    let series_id = series.id.ok_or("Series has no ID")?;
+    let now = Utc::now();
+
+    let occurrences = series
+        .occurrences()
+        .into_iter()
+        .filter(|occurrence| occurrence.start_time > now)
+        .collect::<Vec<_>>();
+
+    let existing = state
+        .db
+        .get_series_practices(series_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|practice| practice.is_future())
+        .collect::<Vec<_>>();
+
+    // Cancelled practices stay in `existing` so their dates aren't recreated,
+    // but are never moved or cancelled again
+    let mut moved = Vec::new();
+    for mut practice in existing.iter().cloned() {
+        if practice.is_cancelled() {
+            continue;
+        }
+        let practice_id = practice.id.ok_or("Practice has no ID")?;
+
+        match occurrences
+            .iter()
+            .find(|occurrence| occurrence.date == practice.local_date())
+        {
+            Some(occurrence) => {
+                if practice.start_time == occurrence.start_time
+                    && practice.end_time == occurrence.end_time
+                {
+                    continue;
+                }
+
+                info!("Moving practice {} to {}", practice_id, occurrence.start_time);
+                let previous_start = practice.start_time;
+                practice.reschedule(occurrence.start_time, occurrence.end_time);
+
+                state
+                    .db
+                    .update_practice_schedule(&mut practice)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                state
+                    .scheduler
+                    .schedule_practice(&practice)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                moved.push(practice_id);
+
+                let message = format!(
+                    "Practice on {} has been moved to {}",
+                    previous_start
+                        .with_timezone(&New_York)
+                        .format("%A, %B %d at %I:%M %p"),
+                    practice
+                        .start_time
+                        .with_timezone(&New_York)
+                        .format("%I:%M %p")
+                );
+                notify_users(&state.db, practice.participants(), &message).await;
+            }
+            None => {
+                info!("Cancelling practice {} no longer in series", practice_id);
+                cancel_and_notify(
+                    state,
+                    &mut practice,
+                    "Removed from the practice schedule".to_string(),
+                )
+                .await?;
+            }
+        }
+    }
+
+    // Relinked once everything has moved, with fresh copies since relinking
+    // one practice can change the links of another
+    for practice_id in moved {
+        if let Some(mut practice) = state
+            .db
+            .get_practice(practice_id)
+            .await
+            .map_err(|e| e.to_string())?
+        {
+            state
+                .db
+                .relink_waitlist_feed(&mut practice)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    let settings = state
+        .db
+        .get_club_settings()
+        .await
+        .map_err(|e| e.to_string())?;
+    let window = settings.signup_window(series.practice_type);
+
+    for occurrence in occurrences.iter().filter(|occurrence| {
+        !existing
+            .iter()
+            .any(|practice| practice.local_date() == occurrence.date)
+    }) {
+        let mut practice = Practice::new(
+            series.practice_type,
+            occurrence.start_time,
+            occurrence.start_time,
+            occurrence.end_time,
+            &window,
+        )
+        .map_err(|e| e.to_string())?;
+        practice.set_early_access(settings.early_access.clone());
+        practice.series_id = Some(series_id);
+
+        let practice_id = state
+            .db
+            .create_practice(&practice)
+            .await
+            .map_err(|e| e.to_string())?;
+        practice.id = Some(practice_id);
+
+        state
+            .db
+            .link_waitlist_feed(&mut practice)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        info!("Created practice {} for series {}", practice_id, series_id);
+        state
+            .scheduler
+            .schedule_practice(&practice)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if series.create_sheet_tabs {
+            if let Err(e) = state.practice_client.create_practice_tab(&practice).await {
+                error!("Failed to create sheet tab for practice {}: {}", practice_id, e);
+            }
+        }
+    }
+
+    Ok(())
+}
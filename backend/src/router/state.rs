@@ -0,0 +1,25 @@
+use axum::extract::FromRef;
+use std::sync::Arc;
+
+use crate::db::db::DB;
+use crate::jobs::scheduler::SchedulerManager;
+use crate::sheets::sheets::SheetsClient;
+
+#[derive(Clone)]
+pub struct AppState {
+  pub db: Arc<DB>,
+  pub scheduler: Arc<SchedulerManager>,
+  pub practice_client: Arc<SheetsClient>
+}
+
+impl FromRef<AppState> for Arc<DB> {
+  fn from_ref(state: &AppState) -> Self {
+    state.db.clone()
+  }
+}
+
+impl FromRef<AppState> for Arc<SchedulerManager> {
+  fn from_ref(state: &AppState) -> Self {
+    state.scheduler.clone()
+  }
+}
@@ -0,0 +1,473 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use mongodb::bson::oid::ObjectId;
+use std::{collections::HashMap, sync::Arc};
+use tracing::info;
+
+use crate::{
+    db::{
+        db::DB,
+        practice::{Placement, Practice},
+        user::{MembershipStatus, Permission, User, UserType, WaiverStatus},
+    },
+    notifications::notify_users,
+};
+
+use super::{
+    auth::Actor,
+    requests::{
+        ReviewWaiverRequest, SetCompetitiveRosterRequest, SetMembershipRequest,
+        SetPenaltyWaiverRequest, SetQualificationsRequest, SetRoleRequest,
+        UpdatePaddlingProfileRequest, UpdateProfileRequest,
+    },
+    responses::{
+        MemberPractice, MemberPracticesResponse, PenaltyStatusResponse, PendingSeatOffer,
+        PracticeStartInfo,
+    },
+};
+
+/// SSMU waivers are signed once per year.
+const WAIVER_VALID_DAYS: i64 = 365;
+
+const MAX_DISPLAY_NAME_LEN: usize = 32;
+
+/// How far back a member's past practices are listed.
+const RECENT_PRACTICE_DAYS: i64 = 14;
+
+/// The profile of the member making the request.
+pub(crate) async fn get_me(actor: Actor) -> Json<User> {
+    Json(actor.user)
+}
+
+/// Lets members change their own side, display name and notification
+/// settings. Fields left out of the request are unchanged, and signups
+/// already made keep their seats.
+pub(crate) async fn update_me(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Json(req): Json<UpdateProfileRequest>,
+) -> Result<Json<User>, String> {
+    let mut user = actor.user;
+
+    if let Some(side) = req.side {
+        user.side = side;
+    }
+    if let Some(display_name) = req.display_name {
+        let display_name = display_name.trim();
+        if display_name.chars().count() > MAX_DISPLAY_NAME_LEN {
+            return Err(format!(
+                "Display names can be at most {} characters",
+                MAX_DISPLAY_NAME_LEN
+            ));
+        }
+        user.display_name = Some(display_name.to_string()).filter(|name| !name.is_empty());
+    }
+    if let Some(notifications) = req.notifications {
+        user.notifications = notifications;
+    }
+
+    info!("User {:?} updated their profile", user.id);
+    db.update_user(&user).await.map_err(|e| e.to_string())?;
+
+    Ok(Json(user))
+}
+
+pub(crate) async fn set_qualifications(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(user_id): Path<String>,
+    Json(req): Json<SetQualificationsRequest>,
+) -> Result<Json<User>, String> {
+    actor.require(Permission::ManageMembers)?;
+
+    let user_id = ObjectId::parse_str(&user_id).map_err(|e| e.to_string())?;
+
+    let mut user = db
+        .get_user(user_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("User not found")?;
+
+    info!(
+        "Setting qualifications of user {} to {:?}",
+        user_id, req.qualifications
+    );
+    user.qualifications = req.qualifications;
+    db.update_user(&user).await.map_err(|e| e.to_string())?;
+
+    Ok(Json(user))
+}
+
+/// Sets the weight and experience the lineup builder seats a paddler by.
+/// Fields left out of the request are unchanged.
+pub(crate) async fn update_paddling_profile(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(user_id): Path<String>,
+    Json(req): Json<UpdatePaddlingProfileRequest>,
+) -> Result<Json<User>, String> {
+    let user_id = ObjectId::parse_str(&user_id).map_err(|e| e.to_string())?;
+    if actor.user.id != Some(user_id) {
+        actor.require(Permission::ManageMembers)?;
+    }
+
+    let mut user = db
+        .get_user(user_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("User not found")?;
+
+    if let Some(weight_kg) = req.weight_kg {
+        if !weight_kg.is_finite() || weight_kg <= 0.0 {
+            return Err("Weight must be positive".to_string());
+        }
+        user.weight_kg = Some(weight_kg);
+    }
+    if let Some(experience) = req.experience {
+        user.experience = Some(experience);
+    }
+
+    info!("Updated paddling profile of user {}", user_id);
+    db.update_user(&user).await.map_err(|e| e.to_string())?;
+
+    Ok(Json(user))
+}
+
+/// The practices a member is on, upcoming ones first and then those from the
+/// last couple of weeks, most recent first, along with the seat offers on
+/// them still waiting on an answer.
+pub(crate) async fn get_member_practices(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(discord_id): Path<String>,
+) -> Result<Json<MemberPracticesResponse>, String> {
+    actor.require_self_or(&discord_id, Permission::EditRosters)?;
+
+    let user = db
+        .get_user_by_discord_id(&discord_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("User not found")?;
+    let user_id = user.id.ok_or("User has no ID")?;
+    let tiers = user.signup_tiers();
+
+    let since = Utc::now() - Duration::days(RECENT_PRACTICE_DAYS);
+    let practices = db
+        .get_member_practices(user_id, since)
+        .await
+        .map_err(|e| e.to_string())?;
+    let offers = pending_seat_offers(&db, user_id, &practices).await?;
+
+    let mut response = MemberPracticesResponse {
+        upcoming: Vec::new(),
+        recent: Vec::new(),
+    };
+    for practice in practices {
+        let Some(placement) = practice.placements().remove(&user_id) else {
+            continue;
+        };
+
+        let waitlist_position = match &placement {
+            Placement::Waitlist { side, position } => {
+                Some(practice.waitlist(side)[..*position].iter().flatten().count() + 1)
+            }
+            _ => None,
+        };
+
+        let entry = MemberPractice {
+            practice: PracticeStartInfo::from(&practice),
+            side: placement.side(),
+            on_waitlist: waitlist_position.is_some(),
+            waitlist_position,
+            placement,
+            signup_closes_at: practice.signup_closes_at,
+            locked: practice.is_locked_for(&tiers),
+            cancelled: practice.is_cancelled(),
+            seat_offers: offers
+                .iter()
+                .filter(|pending| {
+                    practice.id.is_some_and(|id| {
+                        id == pending.offer.practice_id
+                            || Some(id) == pending.offer.swap_practice_id
+                    })
+                })
+                .cloned()
+                .collect(),
+        };
+
+        if practice.is_future() {
+            response.upcoming.push(entry);
+        } else {
+            response.recent.push(entry);
+        }
+    }
+    response.recent.reverse();
+
+    Ok(Json(response))
+}
+
+/// A member's pending seat offers with when they can be claimed by. Offers
+/// touching a practice missing from `practices`, like the other side of a
+/// swap, read that practice for its start.
+async fn pending_seat_offers(
+    db: &DB,
+    user_id: ObjectId,
+    practices: &[Practice],
+) -> Result<Vec<PendingSeatOffer>, String> {
+    let offers = db
+        .get_pending_seat_offers(user_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // None for practices that no longer exist
+    let mut starts: HashMap<ObjectId, Option<DateTime<Utc>>> = practices
+        .iter()
+        .filter_map(|practice| Some((practice.id?, Some(practice.start_time))))
+        .collect();
+
+    let mut pending = Vec::new();
+    for offer in offers {
+        let mut claim_by = None;
+        for practice_id in std::iter::once(offer.practice_id).chain(offer.swap_practice_id) {
+            let start = match starts.get(&practice_id) {
+                Some(start) => *start,
+                None => {
+                    let start = db
+                        .get_practice(practice_id)
+                        .await
+                        .map_err(|e| e.to_string())?
+                        .map(|practice| practice.start_time);
+                    starts.insert(practice_id, start);
+                    start
+                }
+            };
+            let Some(start) = start else {
+                claim_by = None;
+                break;
+            };
+            claim_by = Some(claim_by.map_or(start, |deadline: DateTime<Utc>| deadline.min(start)));
+        }
+
+        if let Some(claim_by) = claim_by {
+            pending.push(PendingSeatOffer { offer, claim_by });
+        }
+    }
+
+    Ok(pending)
+}
+
+/// Shows whether a member's no-shows currently cost them signup priority.
+pub(crate) async fn get_penalty_status(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(user_id): Path<String>,
+) -> Result<Json<PenaltyStatusResponse>, String> {
+    let user_id = ObjectId::parse_str(&user_id).map_err(|e| e.to_string())?;
+    actor.require_user_or(user_id, Permission::ManageMembers)?;
+
+    let user = db
+        .get_user(user_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("User not found")?;
+
+    let settings = db.get_club_settings().await.map_err(|e| e.to_string())?;
+    let history = db
+        .get_user_attendance(user_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Json(PenaltyStatusResponse {
+        penalty: settings.no_show_penalty(&history, Utc::now()),
+        waived_until: user.penalty_waived_until,
+    }))
+}
+
+/// Lets an exec lift a member's no-show penalty until a given time, or
+/// reinstate it by clearing the waiver.
+pub(crate) async fn set_penalty_waiver(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(user_id): Path<String>,
+    Json(req): Json<SetPenaltyWaiverRequest>,
+) -> Result<Json<User>, String> {
+    actor.require(Permission::ManageMembers)?;
+
+    let user_id = ObjectId::parse_str(&user_id).map_err(|e| e.to_string())?;
+
+    let mut user = db
+        .get_user(user_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("User not found")?;
+
+    info!(
+        "Waiving no-show penalties of user {} until {:?}",
+        user_id, req.until
+    );
+    user.penalty_waived_until = req.until;
+    db.update_user(&user).await.map_err(|e| e.to_string())?;
+
+    Ok(Json(user))
+}
+
+pub(crate) async fn set_competitive_roster(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(user_id): Path<String>,
+    Json(req): Json<SetCompetitiveRosterRequest>,
+) -> Result<Json<User>, String> {
+    actor.require(Permission::EditRosters)?;
+
+    let user_id = ObjectId::parse_str(&user_id).map_err(|e| e.to_string())?;
+
+    let mut user = db
+        .get_user(user_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("User not found")?;
+
+    info!(
+        "Setting competitive roster of user {} to {}",
+        user_id, req.competitive_roster
+    );
+    user.competitive_roster = req.competitive_roster;
+    db.update_user(&user).await.map_err(|e| e.to_string())?;
+
+    Ok(Json(user))
+}
+
+/// Records a payment confirmed by an exec, or marks a membership expired.
+pub(crate) async fn set_membership(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(user_id): Path<String>,
+    Json(req): Json<SetMembershipRequest>,
+) -> Result<Json<User>, String> {
+    actor.require(Permission::ManageMembers)?;
+
+    let user_id = ObjectId::parse_str(&user_id).map_err(|e| e.to_string())?;
+
+    let mut user = db
+        .get_user(user_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("User not found")?;
+
+    info!(
+        "Setting membership of user {} to {:?} ({:?})",
+        user_id, req.status, req.plan
+    );
+    let membership = &mut user.membership;
+    if req.status == MembershipStatus::Paid && membership.status != MembershipStatus::Paid {
+        membership.paid_at = Some(Utc::now());
+    }
+    membership.status = req.status;
+    membership.plan = req.plan.or(membership.plan);
+    membership.expires_at = req.expires_at;
+    db.update_user(&user).await.map_err(|e| e.to_string())?;
+
+    Ok(Json(user))
+}
+
+/// An exec checking an uploaded waiver. The member is told either way.
+pub(crate) async fn review_waiver(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(user_id): Path<String>,
+    Json(req): Json<ReviewWaiverRequest>,
+) -> Result<Json<User>, String> {
+    actor.require(Permission::ManageMembers)?;
+
+    let user_id = ObjectId::parse_str(&user_id).map_err(|e| e.to_string())?;
+
+    let mut user = db
+        .get_user(user_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("User not found")?;
+
+    let now = Utc::now();
+    let waiver = &mut user.waiver;
+    waiver.reviewed_at = Some(now);
+
+    let message = if req.verified {
+        let expires_at = req.expires_at.unwrap_or(now + Duration::days(WAIVER_VALID_DAYS));
+        if expires_at <= now {
+            return Err("Waiver expiry must be in the future".to_string());
+        }
+
+        waiver.status = WaiverStatus::Verified;
+        waiver.expires_at = Some(expires_at);
+        waiver.rejection_reason = None;
+        format!(
+            "Your waiver has been verified, you can sign up for practices until {}",
+            expires_at.format("%B %d, %Y")
+        )
+    } else {
+        waiver.status = WaiverStatus::Rejected;
+        waiver.expires_at = None;
+        waiver.rejection_reason = req.reason;
+        waiver.signup_blocker(now).unwrap_or_default()
+    };
+
+    info!("Waiver of user {} reviewed: {:?}", user_id, user.waiver.status);
+    db.update_user(&user).await.map_err(|e| e.to_string())?;
+    notify_users(&db, [user_id], &message).await;
+
+    Ok(Json(user))
+}
+
+/// Grants a role. The first admins come from `ADMIN_DISCORD_IDS`, which
+/// grants them Admin on every request without storing it, so removing an ID
+/// from the variable revokes it.
+pub(crate) async fn set_role(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(user_id): Path<String>,
+    Json(req): Json<SetRoleRequest>,
+) -> Result<Json<User>, String> {
+    actor.require(Permission::GrantRoles)?;
+
+    change_role(&db, &actor, &user_id, req.role).await.map(Json)
+}
+
+/// Takes a member back to a regular role.
+pub(crate) async fn revoke_role(
+    actor: Actor,
+    State(db): State<Arc<DB>>,
+    Path(user_id): Path<String>,
+) -> Result<Json<User>, String> {
+    actor.require(Permission::GrantRoles)?;
+
+    change_role(&db, &actor, &user_id, UserType::Regular)
+        .await
+        .map(Json)
+}
+
+async fn change_role(db: &DB, actor: &Actor, user_id: &str, role: UserType) -> Result<User, String> {
+    let user_id = ObjectId::parse_str(user_id).map_err(|e| e.to_string())?;
+
+    // Otherwise the last admin could lock everyone out of role management
+    if actor.user.id == Some(user_id) {
+        return Err("You can't change your own role".to_string());
+    }
+
+    let mut user = db
+        .get_user(user_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("User not found")?;
+
+    info!(
+        "User {:?} changed the role of user {} from {:?} to {:?}",
+        actor.user.id, user_id, user.user_type, role
+    );
+    user.user_type = role;
+    db.update_user(&user).await.map_err(|e| e.to_string())?;
+
+    Ok(user)
+}
@@ -3,22 +3,35 @@ use serde::{Deserialize, Serialize};
 use chrono::{
   TimeZone,DateTime, Datelike, NaiveDateTime, Utc};
 use std::error::Error;
-use tracing::info;
 
 #[derive(Debug, Deserialize)]
 pub struct FormResponse {
+    #[allow(dead_code)]
     pub email_address: String,
     pub full_name: String,
     pub mcgill_id: String,
     pub preferred_email: String,
+    pub experience_level: String,
     pub paddle_side: String,
+    pub membership_option: String,
+    pub e_transfer_sent: bool,
+    pub waiver_upload: String,
+}
+
+/// The roster block of one boat on a practice tab.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SheetBoat {
+    /// Written above the block when a practice has more than one boat.
+    pub name: Option<String>,
+    pub left_side: Vec<Option<String>>,
+    pub right_side: Vec<Option<String>>
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct PracticeSheetData {
     pub date: DateTime<Utc>,
-    pub left_side: Vec<Option<String>>,
-    pub right_side: Vec<Option<String>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub boats: Vec<SheetBoat>,
     pub left_waitlist: Vec<Option<String>>,
     pub right_waitlist: Vec<Option<String>>
 }
@@ -30,6 +43,9 @@ pub struct SheetMetaData {
   pub last_processed_row: usize
 }
 
+/// Start time and, when the title has one, end time of a practice.
+type PracticeTimes = (DateTime<Utc>, Option<DateTime<Utc>>);
+
 impl PracticeSheetData {
   pub fn parse_from_rows(rows: Vec<Vec<String>>) -> Result<Self, Box<dyn Error>> {
       if rows.is_empty() {
@@ -41,133 +57,128 @@ impl PracticeSheetData {
       // Parse the date from the first row (which is a single-element vector)
       let date_str = &rows[0][0];
       tracing::info!("{:?}", date_str);
-      let date = Self::parse_practice_date(date_str)?;
+      let (date, end_time) = Self::parse_practice_times(date_str)?;
 
-      let mut left_side = Vec::new();
-      let mut right_side = Vec::new();
+      let mut boats: Vec<SheetBoat> = Vec::new();
       let mut left_waitlist = Vec::new();
       let mut right_waitlist = Vec::new();
 
       let mut in_main_list = false;
       let mut in_waitlist = false;
 
-      // Iterate through rows
-      for row in rows.iter() {
+      // Iterate through rows, the first one being the title
+      for (i, row) in rows.iter().enumerate().skip(1) {
           // Skip empty rows
           if row.is_empty() {
               continue;
           }
 
-          // Check for section markers
-          if row.get(1).map_or(false, |cell| cell == "LEFTIES") {
-              tracing::info!("Found main list section");
+          // Check for section markers, sided boats head their block with
+          // LEFTIES and fitness sessions with PARTICIPANTS
+          if row.get(1).is_some_and(|cell| cell == "LEFTIES" || cell == "PARTICIPANTS") {
+              // A boat's name sits alone on the row above its block
+              let name = (i > 1).then(|| Self::label(&rows[i - 1])).flatten();
+              tracing::info!("Found main list section for boat {:?}", name);
+              boats.push(SheetBoat {
+                  name,
+                  ..Default::default()
+              });
               in_main_list = true;
               in_waitlist = false;
               continue;
           }
 
-          if row.get(1).map_or(false, |cell| cell.contains("WAITLIST")) {
+          if row.iter().take(2).any(|cell| cell.contains("WAITLIST")) {
               tracing::info!("Found waitlist section");
               in_main_list = false;
               in_waitlist = true;
               continue;
           }
 
-          if row.get(1).map_or(false, |cell| cell.contains("DO NOT SIGN UP")) {
+          if row.get(1).is_some_and(|cell| cell.contains("DO NOT SIGN UP")) {
               in_main_list = false;
               in_waitlist = false;
               continue;
           }
 
-          // Process main list
-          if in_main_list {
-              // Skip header row with "First Name, Last Name"
-              if row.get(1).map_or(false, |cell| cell == "First Name") {
-                  continue;
-              }
-
-              // Only process numbered rows (1-17)
-              if let Some(first_cell) = row.get(0) {
-                  if first_cell.parse::<u32>().is_ok() {
-                      // Get left side entry
-                      let left_entry = match (row.get(1), row.get(2)) {
-                          (Some(first), Some(last)) if !first.is_empty() && !last.is_empty() => {
-                              Some(format!("{} {}", first.trim(), last.trim()))
-                          }
-                          _ => None
-                      };
-                      left_side.push(left_entry);
-
-                      // Get right side entry
-                      let right_entry = match (row.get(5), row.get(6)) {
-                          (Some(first), Some(last)) if !first.is_empty() && !last.is_empty() => {
-                              Some(format!("{} {}", first.trim(), last.trim()))
-                          }
-                          _ => None
-                      };
-                      right_side.push(right_entry);
-                  }
-              }
+          // Only numbered rows hold names
+          if row[0].parse::<u32>().is_err() {
+              continue;
           }
+          let (left_entry, right_entry) = Self::row_entries(row);
 
-          // Process waitlist
-          if in_waitlist {
-              // Only process numbered rows (1-6)
-              if let Some(first_cell) = row.get(0) {
-                  if first_cell.parse::<u32>().is_ok() {
-                      // Get left side waitlist entry
-                      let left_entry = match (row.get(1), row.get(2)) {
-                          (Some(first), Some(last)) if !first.is_empty() && !last.is_empty() => {
-                              Some(format!("{} {}", first.trim(), last.trim()))
-                          }
-                          _ => None
-                      };
-                      left_waitlist.push(left_entry);
-
-                      // Get right side waitlist entry
-                      let right_entry = match (row.get(5), row.get(6)) {
-                          (Some(first), Some(last)) if !first.is_empty() && !last.is_empty() => {
-                              Some(format!("{} {}", first.trim(), last.trim()))
-                          }
-                          _ => None
-                      };
-                      right_waitlist.push(right_entry);
-                  }
+          // Process main list
+          if in_main_list {
+              if let Some(boat) = boats.last_mut() {
+                  boat.left_side.push(left_entry);
+                  boat.right_side.push(right_entry);
               }
+          } else if in_waitlist {
+              left_waitlist.push(left_entry);
+              right_waitlist.push(right_entry);
           }
       }
 
       // Ensure lists have the correct length
-      while left_side.len() < 17 { left_side.push(None); }
-      while right_side.len() < 17 { right_side.push(None); }
+      if boats.is_empty() {
+          boats.push(SheetBoat::default());
+      }
+      for boat in &mut boats {
+          while boat.left_side.len() < 17 { boat.left_side.push(None); }
+          while boat.right_side.len() < 17 { boat.right_side.push(None); }
+      }
       while left_waitlist.len() < 6 { left_waitlist.push(None); }
       while right_waitlist.len() < 6 { right_waitlist.push(None); }
 
       tracing::info!("Successfully parsed sheet data");
-      tracing::debug!("Left side entries: {}", left_side.len());
-      tracing::debug!("Right side entries: {}", right_side.len());
+      tracing::debug!("Boats: {}", boats.len());
       tracing::debug!("Left waitlist entries: {}", left_waitlist.len());
       tracing::debug!("Right waitlist entries: {}", right_waitlist.len());
 
       Ok(Self {
           date,
-          left_side,
-          right_side,
+          end_time,
+          boats,
           left_waitlist,
           right_waitlist,
       })
   }
 
-  fn parse_practice_date(date_str: &str) -> Result<DateTime<Utc>, Box<dyn Error>> {
+  /// The names in a numbered row, left side in columns B-C and right side in
+  /// columns F-G.
+  fn row_entries(row: &[String]) -> (Option<String>, Option<String>) {
+      let entry = |first: Option<&String>, last: Option<&String>| match (first, last) {
+          (Some(first), Some(last)) if !first.is_empty() && !last.is_empty() => {
+              Some(format!("{} {}", first.trim(), last.trim()))
+          }
+          _ => None
+      };
+
+      (entry(row.get(1), row.get(2)), entry(row.get(5), row.get(6)))
+  }
+
+  /// The text of a row holding only a label in its first column.
+  fn label(row: &[String]) -> Option<String> {
+      let (first, rest) = row.split_first()?;
+      let first = first.trim();
+
+      (!first.is_empty() && first.parse::<u32>().is_err() && rest.iter().all(|cell| cell.trim().is_empty()))
+          .then(|| first.to_string())
+  }
+
+  fn parse_practice_times(
+      date_str: &str,
+  ) -> Result<PracticeTimes, Box<dyn Error>> {
       // Remove quotes and trim whitespace
       let date_str = date_str.trim_matches('"').trim();
 
       tracing::info!("Parsing date string: {}", date_str);
 
-      // Expected format: "Thursday, November 28 (7:00 PM)"
+      // Expected format: "Thursday, November 28 (7:00 PM)" or
+      // "Thursday, November 28 (7:00 PM - 9:00 PM)"
       let mut parts = date_str.rsplitn(2, '(');
 
-      // Get time part: "7:00 PM)"
+      // Get time part: "7:00 PM)" or "7:00 PM - 9:00 PM)"
       let time_part = parts.next()
           .ok_or("Missing time part")?
           .trim()
@@ -188,19 +199,98 @@ impl PracticeSheetData {
       let month_day = date_components.last()
           .ok_or("Missing month and day")?;
 
+      let mut times = time_part.splitn(2, '-').map(|s| s.trim());
+      let start_str = times.next().ok_or("Missing start time")?;
+      let start = Self::parse_local_time(month_day, start_str)?;
+
+      let end = match times.next() {
+          Some(end_str) => {
+              let end = Self::parse_local_time(month_day, end_str)?;
+              if end <= start {
+                  return Err(format!("End time {} is not after start time {}", end, start).into());
+              }
+              Some(end)
+          }
+          None => None,
+      };
+
+      Ok((start, end))
+  }
+
+  fn parse_local_time(month_day: &str, time: &str) -> Result<DateTime<Utc>, Box<dyn Error>> {
       // Combine all parts into a format chrono can parse
       // Add the current year since it's not in the input
       let current_year = Utc::now().year();
-      let datetime_str = format!("{} {} {}", month_day, time_part, current_year);
+      let datetime_str = format!("{} {} {}", month_day, time, current_year);
 
       tracing::info!("Formatted datetime string: {}", datetime_str);
 
       // Parse using a simpler format string
       let naive_dt = NaiveDateTime::parse_from_str(&datetime_str, "%B %d %I:%M %p %Y")?;
-      let est = New_York.from_local_datetime(&naive_dt).unwrap();
+      let est = New_York
+          .from_local_datetime(&naive_dt)
+          .earliest()
+          .ok_or("Time does not exist in local timezone")?;
 
       let utc = est.with_timezone(&Utc);
       tracing::info!("Converted time - EST: {}, UTC: {}", est, utc);
       Ok(utc)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn row(cells: &[&str]) -> Vec<String> {
+      cells.iter().map(|cell| cell.to_string()).collect()
+  }
+
+  #[test]
+  fn parses_one_block_per_boat() {
+      let rows = vec![
+          row(&["Thursday, November 28 (7:00 PM - 9:00 PM)"]),
+          row(&[]),
+          row(&[]),
+          row(&["BOAT 1"]),
+          row(&["", "LEFTIES", "", "", "", "RIGHTIES"]),
+          row(&["1", "Ada", "Lovelace", "", "", "Alan", "Turing"]),
+          row(&["2", "", "", "", "", "", ""]),
+          row(&[]),
+          row(&["BOAT 2"]),
+          row(&["", "LEFTIES", "", "", "", "RIGHTIES"]),
+          row(&["1", "Grace", "Hopper", "", "", "", ""]),
+          row(&[]),
+          row(&["WAITLIST", "", "", "", "", ""]),
+          row(&["1", "", "", "", "", "Edsger", "Dijkstra"]),
+      ];
+
+      let data = PracticeSheetData::parse_from_rows(rows).unwrap();
+
+      assert_eq!(data.boats.len(), 2);
+      assert_eq!(data.boats[0].name.as_deref(), Some("BOAT 1"));
+      assert_eq!(data.boats[0].left_side[0].as_deref(), Some("Ada Lovelace"));
+      assert_eq!(data.boats[0].right_side[0].as_deref(), Some("Alan Turing"));
+      assert_eq!(data.boats[0].left_side[1], None);
+      assert_eq!(data.boats[1].name.as_deref(), Some("BOAT 2"));
+      assert_eq!(data.boats[1].left_side[0].as_deref(), Some("Grace Hopper"));
+      assert_eq!(data.right_waitlist[0].as_deref(), Some("Edsger Dijkstra"));
+  }
+
+  #[test]
+  fn single_boat_and_fitness_blocks_have_no_name() {
+      let rows = vec![
+          row(&["Thursday, November 28 (7:00 PM)"]),
+          row(&["CANCELLED: weather"]),
+          row(&[]),
+          row(&["", "PARTICIPANTS", "", "", "", ""]),
+          row(&["1", "Ada", "Lovelace", "", "", "", ""]),
+      ];
+
+      let data = PracticeSheetData::parse_from_rows(rows).unwrap();
+
+      assert_eq!(data.boats.len(), 1);
+      assert_eq!(data.boats[0].name, None);
+      assert_eq!(data.boats[0].left_side[0].as_deref(), Some("Ada Lovelace"));
+  }
+}
@@ -1,13 +1,21 @@
-use crate::db::practice::Practice;
+use crate::db::audit::{AuditAction, AuditSource, RosterChange};
+use crate::db::practice::{Practice, PracticeType};
 use crate::sheets::sheets::hyper_util::client::legacy::Client;
-use google_sheets4::api::ValueRange;
+use chrono::Utc;
+use chrono_tz::America::New_York;
+use google_sheets4::api::{
+    AddSheetRequest, BatchUpdateSpreadsheetRequest, Request, SheetProperties,
+    UpdateSheetPropertiesRequest, ValueRange,
+};
 use google_sheets4::hyper_rustls::HttpsConnector;
 use google_sheets4::{
     hyper_util::{self, client::legacy::connect::HttpConnector, rt::TokioExecutor},
-    Sheets,
+    FieldMask, Sheets,
 };
 use hyper_rustls;
+use mongodb::bson::oid::ObjectId;
 use serde_json::Value as JsonValue;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Once;
 use std::{env, error::Error};
@@ -96,6 +104,7 @@ impl SheetsClient {
         Ok(sheets_client)
     }
 
+    #[allow(dead_code)]
     pub async fn init_fitness_client(db: Arc<DB>) -> Result<Self, Box<dyn Error>> {
         info!("Initing a fitness sheets client");
         let credentials_path =
@@ -202,8 +211,7 @@ impl SheetsClient {
         Ok(())
     }
 
-    pub async fn fetch_new_form_responses(&self) -> Result<Vec<FormResponse>, Box<dyn Error>> {
-        info!("Fetching new form responses");
+    async fn fetch_form_rows(&self) -> Result<Vec<Vec<JsonValue>>, Box<dyn Error>> {
         let result = self
             .service
             .spreadsheets()
@@ -211,30 +219,55 @@ impl SheetsClient {
             .doit()
             .await?;
 
-        let values = match result.1.values {
-            Some(vals) => vals,
-            None => {
-                info!("No new responses found");
-                return Ok(vec![]);
-            }
-        };
+        Ok(result.1.values.unwrap_or_default())
+    }
+
+    fn form_response(row: &[JsonValue]) -> Option<FormResponse> {
+        // Columns A to H are required; the waiver, membership and e-transfer
+        // questions (I to K) were added later and may be blank
+        if row.len() < 8 {
+            return None;
+        }
+
+        Some(FormResponse {
+            email_address: row[1].clone().as_str().unwrap_or_default().to_string(), // Column B
+            full_name: row[2].clone().as_str().unwrap_or_default().to_string(),     // Column C
+            mcgill_id: row[3].clone().as_str().unwrap_or_default().to_string(),     // Column D
+            preferred_email: row[4].clone().as_str().unwrap_or_default().to_string(), // Column E
+            experience_level: row[6].clone().as_str().unwrap_or_default().to_string(), // Column G
+            paddle_side: row[7].clone().as_str().unwrap_or_default().to_string(), // Column H
+            membership_option: row
+                .get(9)
+                .and_then(|cell| cell.as_str())
+                .unwrap_or_default()
+                .to_string(), // Column J
+            e_transfer_sent: row
+                .get(10)
+                .and_then(|cell| cell.as_str())
+                .is_some_and(|answer| answer.trim().to_lowercase().starts_with("yes")), // Column K
+            waiver_upload: row
+                .get(8)
+                .and_then(|cell| cell.as_str())
+                .unwrap_or_default()
+                .to_string(), // Column I
+        })
+    }
+
+    pub async fn fetch_new_form_responses(&self) -> Result<Vec<FormResponse>, Box<dyn Error>> {
+        info!("Fetching new form responses");
+        let values = self.fetch_form_rows().await?;
+        if values.is_empty() {
+            info!("No new responses found");
+            return Ok(vec![]);
+        }
 
         let mut last_row = self.last_row.lock().await;
         let start = *last_row;
         let mut new_responses = Vec::new();
 
         for row in values.iter().skip(start) {
-            // Ensure the row has at least 8 columns (0-based index 7 for column H)
-            if row.len() < 8 {
+            let Some(form_response) = Self::form_response(row) else {
                 continue; // Skip incomplete rows
-            }
-
-            let form_response = FormResponse {
-                email_address: row[1].clone().as_str().unwrap_or_default().to_string(), // Column B
-                full_name: row[2].clone().as_str().unwrap_or_default().to_string(),     // Column C
-                mcgill_id: row[3].clone().as_str().unwrap_or_default().to_string(),     // Column D
-                preferred_email: row[4].clone().as_str().unwrap_or_default().to_string(), // Column E
-                paddle_side: row[7].clone().as_str().unwrap_or_default().to_string(), // Column H
             };
 
             new_responses.push(form_response);
@@ -254,16 +287,63 @@ impl SheetsClient {
         Ok(new_responses)
     }
 
+    /// Members registered before the waiver and membership questions were read
+    /// have the defaults for them, which refuses their signups. Fills those in
+    /// from the rows already processed.
+    pub async fn backfill_form_answers(&self) -> Result<(), Box<dyn Error>> {
+        let values = self.fetch_form_rows().await?;
+        let processed = *self.last_row.lock().await;
+
+        let mut backfilled = 0;
+        for response in values.iter().take(processed).filter_map(|row| Self::form_response(row)) {
+            let Some(mut user) = self.db.get_user_by_email(&response.preferred_email).await? else {
+                continue;
+            };
+            if user.backfill_from_form(&response) {
+                self.db.update_user(&user).await?;
+                backfilled += 1;
+            }
+        }
+
+        info!("Backfilled form answers for {} members", backfilled);
+        Ok(())
+    }
+
     pub async fn initial_practice_sync(&self) -> Result<(), Box<dyn Error>> {
         let practice_data = self.fetch_practice_data().await?;
+        let settings = self
+            .db
+            .get_club_settings()
+            .await
+            .map_err(|e| e.to_string())?;
 
         for data in practice_data {
             tracing::info!("Creating initial practice for date: {}", data.date);
 
             // Only create if practice doesn't exist
             if self.db.get_practice_by_date(data.date).await?.is_none() {
-                let practice = Practice::from_sheet_data(&data);
-                self.db.create_practice(&practice).await?;
+                let mut practice = Practice::from_sheet_data(
+                    &data,
+                    &settings.signup_window(PracticeType::Water),
+                );
+                practice.set_early_access(settings.early_access.clone());
+                practice.id = Some(self.db.create_practice(&practice).await?);
+                self.db
+                    .audit_roster(
+                        None,
+                        &practice,
+                        &RosterChange {
+                            actor: None,
+                            subject: None,
+                            source: AuditSource::SheetSync,
+                            action: AuditAction::SheetImport,
+                        },
+                    )
+                    .await;
+                self.db
+                    .link_waitlist_feed(&mut practice)
+                    .await
+                    .map_err(|e| e.to_string())?;
                 tracing::info!("Successfully created new practice");
             }
         }
@@ -321,23 +401,133 @@ impl SheetsClient {
         Ok(all_practice_data)
     }
 
+    /// Name of the sheet tab holding a practice, e.g. "Nov 28 7 PM".
+    pub fn tab_title(practice: &Practice) -> String {
+        practice
+            .start_time
+            .with_timezone(&New_York)
+            .format("%b %d %-I %p")
+            .to_string()
+    }
+
+    pub async fn create_practice_tab(
+        &self,
+        practice: &Practice,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let title = Self::tab_title(practice);
+        info!("Creating sheet tab {}", title);
+
+        let req = BatchUpdateSpreadsheetRequest {
+            requests: Some(vec![Request {
+                add_sheet: Some(AddSheetRequest {
+                    properties: Some(SheetProperties {
+                        title: Some(title),
+                        ..Default::default()
+                    }),
+                }),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+
+        self.service
+            .spreadsheets()
+            .batch_update(req, &self.sheet_id)
+            .doit()
+            .await?;
+
+        self.update_sheet_from_practice(practice).await
+    }
+
+    /// Renames a cancelled practice's tab and writes the reason under its header.
+    pub async fn mark_practice_tab_cancelled(
+        &self,
+        practice: &Practice,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let title = Self::tab_title(practice);
+
+        let spreadsheet = self
+            .service
+            .spreadsheets()
+            .get(&self.sheet_id)
+            .doit()
+            .await?
+            .1;
+
+        let tab_id = spreadsheet
+            .sheets
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|sheet| sheet.properties)
+            .find(|properties| properties.title.as_deref() == Some(title.as_str()))
+            .and_then(|properties| properties.sheet_id);
+
+        let Some(tab_id) = tab_id else {
+            info!("No sheet tab {} to mark as cancelled", title);
+            return Ok(());
+        };
+
+        let cancelled_title = format!("CANCELLED - {}", title);
+        let req = BatchUpdateSpreadsheetRequest {
+            requests: Some(vec![Request {
+                update_sheet_properties: Some(UpdateSheetPropertiesRequest {
+                    properties: Some(SheetProperties {
+                        sheet_id: Some(tab_id),
+                        title: Some(cancelled_title.clone()),
+                        ..Default::default()
+                    }),
+                    fields: Some(FieldMask::from_str("title")?),
+                }),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+
+        self.service
+            .spreadsheets()
+            .batch_update(req, &self.sheet_id)
+            .doit()
+            .await?;
+
+        let reason = practice
+            .cancellation
+            .as_ref()
+            .map(|cancellation| cancellation.reason.as_str())
+            .unwrap_or_default();
+        let range = format!("'{}'!A2", cancelled_title);
+        let req = ValueRange {
+            range: Some(range.clone()),
+            values: Some(vec![vec![JsonValue::String(format!("CANCELLED: {}", reason))]]),
+            major_dimension: None,
+        };
+
+        self.service
+            .spreadsheets()
+            .values_update(req, &self.sheet_id, &range)
+            .value_input_option("RAW")
+            .doit()
+            .await?;
+
+        Ok(())
+    }
+
     pub async fn update_sheet_from_practice(
         &self,
         practice: &Practice,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
         let values = self.format_practice_for_sheet(practice).await?;
 
-        // Update the sheet with the new values
-        let range = format!("A1:H{}", values.len()); // Adjust range based on your sheet structure
+        // Update the practice's tab with the new values
+        let range = format!("'{}'!A1:H{}", Self::tab_title(practice), values.len());
         let req = ValueRange {
-            range: Some(range),
+            range: Some(range.clone()),
             values: Some(values),
             major_dimension: None,
         };
 
         self.service
             .spreadsheets()
-            .values_update(req, &self.sheet_id, &self.range)
+            .values_update(req, &self.sheet_id, &range)
             .value_input_option("RAW")
             .doit()
             .await?;
@@ -353,43 +543,44 @@ impl SheetsClient {
 
         // Add header row
         values.push(vec![JsonValue::String(format!(
-            "{}",
-            practice.date.format("%A, %B %d (%I:%M %p)")
+            "{} - {})",
+            practice
+                .date
+                .with_timezone(&New_York)
+                .format("%A, %B %d (%I:%M %p"),
+            practice.end_time.with_timezone(&New_York).format("%I:%M %p")
         ))]);
         values.push(vec![]); // Empty row
         values.push(vec![]); // Empty row
 
-        // Add column headers
-        values.push(vec![
-            JsonValue::String("".to_string()),
-            JsonValue::String("LEFTIES".to_string()),
-            JsonValue::String("".to_string()),
-            JsonValue::String("".to_string()),
-            JsonValue::String("".to_string()),
-            JsonValue::String("RIGHTIES".to_string()),
-        ]);
-
-        // Add participant rows
-        for i in 0..17 {
-            let mut row = vec![JsonValue::String("".to_string()); 6];
-
-            // Left side
-            if let Some(user_id) = &practice.left_side[i] {
-                if let Some(user) = self.db.get_user(*user_id).await? {
-                    row[1] = JsonValue::String(user.first_name);
-                    row[2] = JsonValue::String(user.last_name);
-                }
-            }
+        let (left_header, right_header) = if practice.practice_type.has_sides() {
+            ("LEFTIES", "RIGHTIES")
+        } else {
+            ("PARTICIPANTS", "")
+        };
 
-            // Right side
-            if let Some(user_id) = &practice.right_side[i] {
-                if let Some(user) = self.db.get_user(*user_id).await? {
-                    row[5] = JsonValue::String(user.first_name);
-                    row[6] = JsonValue::String(user.last_name);
-                }
+        // One block per boat, named when there's more than one
+        for boat in &practice.boats {
+            if practice.boats.len() > 1 {
+                values.push(vec![JsonValue::String(boat.name.to_uppercase())]);
             }
 
-            values.push(row);
+            // Add column headers
+            values.push(vec![
+                JsonValue::String("".to_string()),
+                JsonValue::String(left_header.to_string()),
+                JsonValue::String("".to_string()),
+                JsonValue::String("".to_string()),
+                JsonValue::String("".to_string()),
+                JsonValue::String(right_header.to_string()),
+            ]);
+
+            // Add participant rows
+            values.extend(
+                self.format_roster_rows(&boat.left_side, &boat.right_side)
+                    .await?,
+            );
+            values.push(vec![]); // Empty row
         }
 
         // Add waitlist header
@@ -403,29 +594,61 @@ impl SheetsClient {
         ]);
 
         // Add waitlist rows
-        for i in 0..6 {
-            let mut row = vec![JsonValue::String("".to_string()); 6];
-
-            // Left side waitlist
-            if let Some(user_id) = &practice.left_side_waitlist[i] {
-                if let Some(user) = self.db.get_user(*user_id).await? {
-                    row[1] = JsonValue::String(user.first_name);
-                    row[2] = JsonValue::String(user.last_name);
+        values.extend(
+            self.format_roster_rows(&practice.left_side_waitlist, &practice.right_side_waitlist)
+                .await?,
+        );
+
+        Ok(values)
+    }
+
+    async fn format_roster_rows(
+        &self,
+        left: &[Option<ObjectId>],
+        right: &[Option<ObjectId>],
+    ) -> Result<Vec<Vec<JsonValue>>, Box<dyn Error + Send + Sync>> {
+        let mut rows = Vec::new();
+
+        for i in 0..left.len().max(right.len()) {
+            let mut row = vec![JsonValue::String("".to_string()); 7];
+            row[0] = JsonValue::String((i + 1).to_string());
+
+            // Left side
+            if let Some(Some(user_id)) = left.get(i) {
+                if let Some((first_name, last_name)) = self.roster_name(*user_id).await? {
+                    row[1] = JsonValue::String(first_name);
+                    row[2] = JsonValue::String(last_name);
                 }
             }
 
-            // Right side waitlist
-            if let Some(user_id) = &practice.right_side_waitlist[i] {
-                if let Some(user) = self.db.get_user(*user_id).await? {
-                    row[5] = JsonValue::String(user.first_name);
-                    row[6] = JsonValue::String(user.last_name);
+            // Right side
+            if let Some(Some(user_id)) = right.get(i) {
+                if let Some((first_name, last_name)) = self.roster_name(*user_id).await? {
+                    row[5] = JsonValue::String(first_name);
+                    row[6] = JsonValue::String(last_name);
                 }
             }
 
-            values.push(row);
+            rows.push(row);
         }
 
-        Ok(values)
+        Ok(rows)
+    }
+
+    /// The name shown for a seat, which may be held by a member or a guest.
+    async fn roster_name(
+        &self,
+        user_id: ObjectId,
+    ) -> Result<Option<(String, String)>, Box<dyn Error + Send + Sync>> {
+        if let Some(user) = self.db.get_user(user_id).await? {
+            return Ok(Some((user.first_name, user.last_name)));
+        }
+
+        Ok(self
+            .db
+            .get_guest(user_id)
+            .await?
+            .map(|guest| (guest.first_name, format!("{} (guest)", guest.last_name))))
     }
 }
 
@@ -436,8 +659,24 @@ pub(crate) async fn fetch_and_add_users(
     let new_responses = sheets_client.fetch_new_form_responses().await?;
 
     for response in new_responses {
-        let user = User::convert_form_to_user(&response)?;
+        let mut user = User::convert_form_to_user(&response)?;
+
+        // A guest registering keeps their ID, and with it their seats
+        let guest = db
+            .get_guest_by_email(&user.email)
+            .await?
+            .filter(|guest| !guest.is_converted());
+        if let Some(guest) = &guest {
+            user.id = guest.id;
+        }
+
         db.create_user_from_sheet(&user).await?;
+
+        if let Some(mut guest) = guest {
+            info!("Guest {} registered as a member", user.email);
+            guest.converted_at = Some(Utc::now());
+            db.update_guest(&guest).await?;
+        }
     }
 
     Ok(())
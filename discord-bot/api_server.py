@@ -1,5 +1,5 @@
 from fastapi import FastAPI, HTTPException, status
-from models import Practice, WaitlistedMessageRequest
+from models import Practice, WaitlistedMessageRequest, DirectMessageRequest
 from discord import Client, Embed, Color
 from datetime import *
 
@@ -63,6 +63,26 @@ async def create_practice(practice: Practice):
             inline=False
         )
 
+        if practice.early_access and practice.signup_opens_at:
+            openings = [
+                f"{opening.tier}: <t:{int(opening.opens_at.timestamp())}:f>"
+                for opening in practice.early_access
+            ]
+            openings.append(f"Everyone: <t:{int(practice.signup_opens_at.timestamp())}:f>")
+
+            practice_embed.add_field(
+                name="⏳ Early Access",
+                value="\n".join(openings),
+                inline=False
+            )
+
+        if practice.lottery_draws_at:
+            practice_embed.add_field(
+                name="🎟️ Lottery",
+                value=f"Seats are drawn at random from everyone who reacts before <t:{int(practice.lottery_draws_at.timestamp())}:f>",
+                inline=False
+            )
+
         practice_embed.add_field(
             name="📝 How to Sign Up",
             value="• React with ✅ to join practice\n"
@@ -110,3 +130,25 @@ async def send_msg_to_waitlisted_user(waitlisted_msg: WaitlistedMessageRequest):
         status_code=status.HTTP_404_NOT_FOUND,
         detail="User not found..."
     )
+
+
+@app.post('/dm', status_code=status.HTTP_201_CREATED)
+async def send_direct_message(dm: DirectMessageRequest):
+    if not discord_client:
+        raise HTTPException(
+            status_code=status.HTTP_500_INTERNAL_SERVER_ERROR,
+            detail="Discord client not initialized..."
+        )
+
+    user = discord_client.get_user(dm.discord_id)
+    if user:
+        await user.send(dm.message)
+        return {
+            "status": "success",
+            "message": "Message sent to user"
+        }
+
+    raise HTTPException(
+        status_code=status.HTTP_404_NOT_FOUND,
+        detail="User not found..."
+    )
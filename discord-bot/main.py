@@ -1,6 +1,9 @@
 from json import JSONDecodeError
+from datetime import datetime
+from zoneinfo import ZoneInfo
 import os
 import asyncio
+import logging
 import uvicorn
 import re
 from typing import Final
@@ -20,6 +23,11 @@ if not TOKEN:
 if not CHANNEL_ID:
     raise ValueError("CHANNEL_ID environment variable is not set")
 URL: Final[str] = os.getenv('BACKEND_API_URL') or 'http://backend:8000'  # Base URL
+API_TOKEN: Final[str] = os.getenv('API_TOKEN') or ''
+if not API_TOKEN:
+    raise ValueError("API_TOKEN environment variable is not set")
+CLUB_TZ: Final[ZoneInfo] = ZoneInfo("America/New_York")
+logger = logging.getLogger(__name__)
 
 # BOT SETUP
 intents: Intents = Intents.default()
@@ -43,6 +51,17 @@ client.channel_id = CHANNEL_ID
 
 waiting_for_email = {}
 
+def backend_headers(discord_id: str | None = None) -> dict:
+    """Authenticates with the backend, acting for the given member if any."""
+    headers = {
+        "Content-Type": "application/json",
+        "Authorization": f"Bearer {API_TOKEN}",
+        "X-Client": "discord-bot"
+    }
+    if discord_id:
+        headers["X-Discord-Id"] = discord_id
+    return headers
+
 def is_valid_email(email: str) -> bool:
     pattern = r'^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}$'
     return bool(re.match(pattern, email))
@@ -54,9 +73,7 @@ async def register_user(user_id: str, email: str):
                 "email": email,
                 "discord_id": user_id
             }
-            headers = {
-                "Content-Type": "application/json"
-            }
+            headers = backend_headers()
             full_url = f"{URL}/register"
             print(f"Sending request to: {full_url}")  # Debug print
             print(f"Payload: {payload}")
@@ -79,9 +96,7 @@ async def sign_up_for_practice(practice_id: str, user_id: str):
                 "practice_id": practice_id,
                 "discord_id": user_id
             }
-            headers = {
-                "Content-Type": "application/json"
-            }
+            headers = backend_headers(user_id)
             full_url = f"{URL}/practice/signup"
 
             print(f"Sending request to: {full_url}")  # Debug print
@@ -104,9 +119,7 @@ async def unregister_from_practice(practice_id: str, user_id: str):
                 "practice_id": practice_id,
                 "discord_id": user_id
             }
-            headers = {
-                "Content-Type": "application/json"
-            }
+            headers = backend_headers(user_id)
             full_url = f"{URL}/practice/unregister"
 
             print(f"Sending unregister request to: {full_url}")  # Debug print
@@ -122,6 +135,107 @@ async def unregister_from_practice(practice_id: str, user_id: str):
         except Exception as e:
             return False, f"Unexpected error: {str(e)}"
 
+async def fetch_lineup(practice_id: str, user_id: str):
+    async with aiohttp.ClientSession() as session:
+        try:
+            full_url = f"{URL}/practice/{practice_id}/lineup"
+            logger.debug("Sending lineup request to: %s", full_url)
+
+            async with session.get(full_url, headers=backend_headers(user_id)) as response:
+                if response.status == 200:
+                    return True, await response.json()
+                else:
+                    response_text = await response.text()
+                    return False, response_text.strip('"')
+        except aiohttp.ClientError as e:
+            return False, f"Failed to connect to backend: {str(e)}"
+        except Exception as e:
+            return False, f"Unexpected error: {str(e)}"
+
+async def fetch_my_signups(user_id: str):
+    async with aiohttp.ClientSession() as session:
+        try:
+            full_url = f"{URL}/users/{user_id}/practices"
+            logger.debug("Sending signups request to: %s", full_url)
+
+            async with session.get(full_url, headers=backend_headers(user_id)) as response:
+                if response.status == 200:
+                    return True, await response.json()
+                else:
+                    response_text = await response.text()
+                    return False, response_text.strip('"')
+        except aiohttp.ClientError as e:
+            return False, f"Failed to connect to backend: {str(e)}"
+        except Exception as e:
+            return False, f"Unexpected error: {str(e)}"
+
+def format_signup(entry: dict) -> str:
+    start = datetime.fromisoformat(entry["practice"]["start_time"]).astimezone(CLUB_TZ)
+    placement = entry["placement"]
+
+    if entry["cancelled"]:
+        where = "cancelled"
+    elif placement["list"] == "Role":
+        where = f"{placement['role']}, boat {placement['boat'] + 1}"
+    elif placement["list"] == "Lottery":
+        where = "in the lottery"
+    elif entry["on_waitlist"]:
+        where = f"waitlist #{entry['waitlist_position']} ({entry['side']})"
+    else:
+        where = f"main list ({entry['side']})"
+
+    line = f"{start:%a %b %d %I:%M %p} - {where}"
+    if not entry["locked"] and not entry["cancelled"]:
+        closes = datetime.fromisoformat(entry["signup_closes_at"]).astimezone(CLUB_TZ)
+        line += f", locks {closes:%a %I:%M %p}"
+    for pending in entry["seat_offers"]:
+        claim_by = datetime.fromisoformat(pending["claim_by"]).astimezone(CLUB_TZ)
+        line += f", seat offer open until {claim_by:%a %I:%M %p}"
+    return line
+
+async def post_relink(path: str, payload: dict):
+    async with aiohttp.ClientSession() as session:
+        try:
+            full_url = f"{URL}{path}"
+            logger.debug("Sending relink request to: %s", full_url)
+
+            async with session.post(full_url, json=payload, headers=backend_headers()) as response:
+                text_response = await response.text()
+                return response.status == 200, text_response.strip('"')
+        except aiohttp.ClientError as e:
+            return False, f"Failed to connect to backend: {str(e)}"
+        except Exception as e:
+            return False, f"Unexpected error: {str(e)}"
+
+async def unlink_account(user_id: str):
+    async with aiohttp.ClientSession() as session:
+        try:
+            full_url = f"{URL}/me/discord"
+            logger.debug("Sending unlink request to: %s", full_url)
+
+            async with session.delete(full_url, headers=backend_headers(user_id)) as response:
+                text_response = await response.text()
+                return response.status == 200, text_response.strip('"')
+        except aiohttp.ClientError as e:
+            return False, f"Failed to connect to backend: {str(e)}"
+        except Exception as e:
+            return False, f"Unexpected error: {str(e)}"
+
+def format_lineup(lineup: dict) -> str:
+    def name(seat):
+        return seat["name"] if seat else "—"
+
+    lines = [lineup["boat"], f"Drummer: {name(lineup['drummer'])}"]
+    for row in lineup["rows"]:
+        lines.append(f"{row['row']:>2}. {name(row['left'])} | {name(row['right'])}")
+    lines.append(f"Steerer: {name(lineup['steerer'])}")
+
+    if lineup["alternates"]:
+        lines.append(f"Alternates: {', '.join(seat['name'] for seat in lineup['alternates'])}")
+
+    lines.append(f"Left/right: {lineup['left_right_kg']:+.1f} kg")
+    return "\n".join(lines)
+
 
 
 # HANDLING BOT STARTUP
@@ -320,9 +434,72 @@ async def schedule(interaction: Interaction):
 
 
 
-@client.tree.command(name="lineup", description="Sends the next practice lineup")
-async def lineup(interaction: Interaction):
-    await interaction.response.send_message(f'Here is the lineup {interaction.user.name}!')
+@client.tree.command(name="lineup", description="Sends the lineup of a practice")
+async def lineup(interaction: Interaction, practice_id: str):
+    success, result = await fetch_lineup(practice_id, str(interaction.user.id))
+
+    if success:
+        await interaction.response.send_message(
+            embed=Embed(
+                title="Practice Lineup",
+                description="\n".join(f"```\n{format_lineup(boat)}\n```" for boat in result),
+                color=Color.blue()
+            )
+        )
+    else:
+        await interaction.response.send_message(f"Couldn't get the lineup: {result}")
+
+
+
+@client.tree.command(name="mysignups", description="Shows the practices you're signed up for")
+async def mysignups(interaction: Interaction):
+    success, result = await fetch_my_signups(str(interaction.user.id))
+
+    if not success:
+        await interaction.response.send_message(f"Couldn't get your signups: {result}", ephemeral=True)
+        return
+
+    embed = Embed(title="Your Signups", color=Color.blue())
+    embed.add_field(
+        name="Upcoming",
+        value="\n".join(format_signup(entry) for entry in result["upcoming"]) or "Nothing yet",
+        inline=False
+    )
+    if result["recent"]:
+        embed.add_field(
+            name="Recent",
+            value="\n".join(format_signup(entry) for entry in result["recent"]),
+            inline=False
+        )
+    await interaction.response.send_message(embed=embed, ephemeral=True)
+
+
+
+@client.tree.command(name="relink", description="Moves your club account to this Discord account")
+async def relink(interaction: Interaction, email: str):
+    _, message = await post_relink("/register/relink", {
+        "email": email.strip(),
+        "discord_id": str(interaction.user.id)
+    })
+    await interaction.response.send_message(message, ephemeral=True)
+
+
+
+@client.tree.command(name="verify_relink", description="Finishes a relink with the code we emailed you")
+async def verify_relink(interaction: Interaction, email: str, code: str):
+    _, message = await post_relink("/register/relink/verify", {
+        "email": email.strip(),
+        "discord_id": str(interaction.user.id),
+        "code": code.strip()
+    })
+    await interaction.response.send_message(message, ephemeral=True)
+
+
+
+@client.tree.command(name="unlink", description="Unlinks this Discord account from your club account")
+async def unlink(interaction: Interaction):
+    _, message = await unlink_account(str(interaction.user.id))
+    await interaction.response.send_message(message, ephemeral=True)
 
 
 
@@ -2,11 +2,22 @@ from pydantic import BaseModel, Field
 from datetime import datetime
 from typing import Optional, List
 
+class EarlyAccessOpening(BaseModel):
+    tier: str
+    opens_at: datetime
+
 class Practice(BaseModel):
     practice_id: str
     start_time: datetime
     end_time: datetime
+    signup_opens_at: Optional[datetime] = None
+    early_access: List[EarlyAccessOpening] = []
+    lottery_draws_at: Optional[datetime] = None
 
 class WaitlistedMessageRequest(BaseModel):
     practice: Practice
     discord_id: int
+
+class DirectMessageRequest(BaseModel):
+    discord_id: int
+    message: str